@@ -2,10 +2,16 @@
 pub enum Error {
     Poly(libpoly::Error),
     Seed,
+    Snapshot,
     StreamConfig(cpal::DefaultStreamConfigError),
     BuildStream(cpal::BuildStreamError),
     PlayStream(cpal::PlayStreamError),
     NoDefaultDevice,
+    MidiInit(midir::InitError),
+    MidiConnect(midir::ConnectErrorKind),
+    MidiPort,
+    Role,
+    Io(std::io::Error),
 }
 
 impl From<libpoly::Error> for Error {
@@ -31,3 +37,21 @@ impl From<cpal::PlayStreamError> for Error {
         Error::PlayStream(error)
     }
 }
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<midir::InitError> for Error {
+    fn from(error: midir::InitError) -> Self {
+        Error::MidiInit(error)
+    }
+}
+
+impl<T> From<midir::ConnectError<T>> for Error {
+    fn from(error: midir::ConnectError<T>) -> Self {
+        Error::MidiConnect(error.kind())
+    }
+}
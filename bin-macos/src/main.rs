@@ -1,6 +1,10 @@
 mod error;
+mod midi;
 
 use std::env;
+use std::fs;
+use std::io;
+use std::sync::mpsc::{self, Receiver};
 use std::thread;
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -9,49 +13,152 @@ use cpal::{
     Sample, SampleFormat,
 };
 
-use libpoly::Poly;
+use libpoly::{Poly, PolyConfig, Role, Snapshot};
 
 use error::Error;
+use midi::{Command, Output, Performance};
 
 pub fn main() -> Result<(), Error> {
     pretty_env_logger::init();
 
-    let seed = env::args()
-        .nth(1)
+    let mut args = env::args().skip(1);
+    let arg = args.next();
+    let snapshot = match arg.as_deref() {
+        Some(arg) if arg.parse::<u64>().is_err() => Some(Snapshot::decode(&fs::read(arg)?)?),
+        _ => None,
+    };
+    let seed = arg
         .and_then(|arg| arg.parse::<u64>().ok())
-        .or_else(|| {
-            SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .ok()
-                .map(|duration| duration.as_secs())
-        })
+        .or_else(now)
         .ok_or(Error::Seed)?;
 
+    let port = args.next();
+    let role = match args.next().as_deref() {
+        None => Role::Internal,
+        Some("follow") if port.is_some() => Role::Follow,
+        Some("lead") if port.is_some() => Role::Lead,
+        Some(_) => return Err(Error::Role),
+    };
+
     let host = cpal::default_host();
     let device = host.default_output_device().ok_or(Error::NoDefaultDevice)?;
     let config = device.default_output_config()?;
 
+    let (sender, commands) = mpsc::channel();
+    let performance = Performance::new(config.sample_rate().0, role);
+    let _connection = port
+        .as_deref()
+        .map(|port| midi::connect(port, performance, sender))
+        .transpose()?;
+    let output = match (role, port.as_deref()) {
+        (Role::Lead, Some(port)) => Some(Output::connect(port)?),
+        _ => None,
+    };
+    let midi = Midi {
+        commands,
+        role,
+        output,
+    };
+
     match config.sample_format() {
-        SampleFormat::I16 => run::<i16>(seed, &device, &config.into()),
-        SampleFormat::U16 => run::<u16>(seed, &device, &config.into()),
-        SampleFormat::F32 => run::<f32>(seed, &device, &config.into()),
+        SampleFormat::I16 => run::<i16>(seed, snapshot, midi, &device, &config.into()),
+        SampleFormat::U16 => run::<u16>(seed, snapshot, midi, &device, &config.into()),
+        SampleFormat::F32 => run::<f32>(seed, snapshot, midi, &device, &config.into()),
     }
 }
 
-pub fn run<T>(seed: u64, device: &cpal::Device, config: &cpal::StreamConfig) -> Result<(), Error>
+fn now() -> Option<u64> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|duration| duration.as_secs())
+}
+
+enum Report {
+    Retired(Box<Poly>),
+    Ignored(libpoly::Error),
+}
+
+pub struct Midi {
+    commands: Receiver<Command>,
+    role: Role,
+    output: Option<Output>,
+}
+
+pub fn run<T>(
+    seed: u64,
+    snapshot: Option<Snapshot>,
+    midi: Midi,
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+) -> Result<(), Error>
 where
     T: Sample + std::fmt::Debug,
 {
-    let mut poly = Poly::new(seed, config.sample_rate.0)?;
+    let mut poly = Box::new(Poly::new(
+        seed,
+        config.sample_rate.0,
+        &PolyConfig::default(),
+    )?);
+    if let Some(snapshot) = snapshot {
+        poly.restore(&snapshot)?;
+    }
+    poly.set_role(midi.role);
+
+    let Midi {
+        commands,
+        mut output,
+        ..
+    } = midi;
+
+    let (request, requests) = mpsc::channel();
+    let (send, snapshots) = mpsc::channel();
+    let (report, reports) = mpsc::channel();
+    thread::spawn(move || {
+        for report in reports {
+            match report {
+                Report::Retired(poly) => drop(poly),
+                Report::Ignored(error) => eprintln!("ignored control: {:?}", error),
+            }
+        }
+    });
 
     let channels = config.channels as usize;
+    let mut frames = Vec::new();
     let stream = device.build_output_stream(
         config,
         move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
-            for frame in data.chunks_mut(channels) {
-                let value: T = cpal::Sample::from::<f32>(&poly.next());
-                for sample in frame.iter_mut() {
-                    *sample = value;
+            for command in commands.try_iter() {
+                let message = match command.apply(&mut poly) {
+                    Ok(None) => continue,
+                    Ok(Some(retired)) => Report::Retired(retired),
+                    Err(error) => Report::Ignored(error),
+                };
+                report.send(message).ok();
+            }
+
+            if requests.try_recv().is_ok() {
+                send.send(poly.snapshot()).ok();
+            }
+
+            frames.resize(data.len() / channels, [0.0; 2]);
+            poly.render_transport(
+                &mut frames,
+                |_| {},
+                |_, transport| {
+                    if let Some(output) = &mut output {
+                        output.send(transport);
+                    }
+                },
+            );
+            for (frame, samples) in frames.iter().zip(data.chunks_exact_mut(channels)) {
+                for (channel, sample) in samples.iter_mut().enumerate() {
+                    let value = if channels == 1 {
+                        (frame[0] + frame[1]) * 0.5
+                    } else {
+                        frame[channel % 2]
+                    };
+                    *sample = cpal::Sample::from::<f32>(&value);
                 }
             }
         },
@@ -60,6 +167,18 @@ where
 
     stream.play()?;
 
+    for line in io::stdin().lines() {
+        line?;
+        request.send(()).map_err(|_| Error::Snapshot)?;
+        let snapshot = snapshots.recv().map_err(|_| Error::Snapshot)?;
+
+        let mut bytes = [0; Snapshot::SIZE];
+        let length = snapshot.encode(&mut bytes)?;
+        let path = format!("{}.poly", now().ok_or(Error::Seed)?);
+        fs::write(&path, &bytes[..length])?;
+        eprintln!("bookmarked {} to {}", snapshot, path);
+    }
+
     loop {
         thread::park()
     }
@@ -0,0 +1,357 @@
+use std::mem;
+use std::sync::mpsc::Sender;
+
+use midir::os::unix::{VirtualInput, VirtualOutput};
+use midir::{Ignore, MidiIO, MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection};
+
+use libpoly::{Poly, PolyConfig, Role, Transport};
+
+use crate::error::Error;
+
+const CLIENT: &str = "Polyhymnia";
+const VIRTUAL: &str = "virtual";
+
+const MUTATION: u8 = 1;
+const TEMPO: u8 = 3;
+const FREEZE: u8 = 64;
+const RESONANCE: u8 = 71;
+const CUTOFF: u8 = 74;
+const MIDDLE: i32 = 60;
+
+const TEMPOS: (f32, f32) = (40.0, 200.0);
+const CUTOFFS: (f32, f32) = (40.0, 8000.0);
+const RESONANCES: (f32, f32) = (0.0, 0.95);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Control {
+    Tempo(f32),
+    Cutoff(f32),
+    Resonance(f32),
+    Mutation(f32),
+    Transpose(i32),
+    Reseed(u64),
+    Freeze(bool),
+    Transport(Transport),
+}
+
+impl Control {
+    pub fn parse(message: &[u8]) -> Option<Self> {
+        if let Some(transport) = Transport::parse(message) {
+            return Some(Control::Transport(transport));
+        }
+
+        match *message {
+            [status, controller, value] if status & 0xF0 == 0xB0 => {
+                let value = value as f32 / 127.0;
+                match controller {
+                    TEMPO => Some(Control::Tempo(linear(TEMPOS, value))),
+                    CUTOFF => Some(Control::Cutoff(exponential(CUTOFFS, value))),
+                    RESONANCE => Some(Control::Resonance(linear(RESONANCES, value))),
+                    MUTATION => Some(Control::Mutation(16.0f32.powf(value * 2.0 - 1.0))),
+                    FREEZE => Some(Control::Freeze(value >= 0.5)),
+                    _ => None,
+                }
+            }
+            [status, note, velocity] if status & 0xF0 == 0x90 && velocity > 0 => {
+                Some(Control::Transpose(note as i32 - MIDDLE))
+            }
+            [status, program] if status & 0xF0 == 0xC0 => Some(Control::Reseed(program as u64)),
+            _ => None,
+        }
+    }
+}
+
+pub enum Command {
+    Tempo(f32),
+    Filter(f32, f32),
+    Mutation(f32),
+    Transpose(i32),
+    Freeze(bool),
+    Transport(Transport),
+    Reseed(Box<Poly>),
+}
+
+impl Command {
+    pub fn apply(self, poly: &mut Box<Poly>) -> Result<Option<Box<Poly>>, libpoly::Error> {
+        match self {
+            Command::Tempo(bpm) => poly.set_tempo(bpm)?,
+            Command::Filter(cutoff, resonance) => {
+                for track in 0..Poly::TRACKS {
+                    poly.set_filter(track, cutoff, resonance)?;
+                }
+            }
+            Command::Mutation(mutation) => poly.set_mutation(mutation)?,
+            Command::Transpose(semitones) => poly.transpose(semitones),
+            Command::Freeze(freeze) => poly.set_locked(freeze),
+            Command::Transport(transport) => poly.receive(transport),
+            Command::Reseed(reseeded) => return Ok(Some(mem::replace(poly, reseeded))),
+        }
+        Ok(None)
+    }
+}
+
+fn linear((low, high): (f32, f32), value: f32) -> f32 {
+    low + (high - low) * value
+}
+
+fn exponential((low, high): (f32, f32), value: f32) -> f32 {
+    low * (high / low).powf(value)
+}
+
+pub struct Performance {
+    sample_rate: u32,
+    role: Role,
+    transpose: i32,
+    cutoff: f32,
+    resonance: f32,
+    mutation: f32,
+    freeze: bool,
+}
+
+impl Performance {
+    pub fn new(sample_rate: u32, role: Role) -> Self {
+        Performance {
+            sample_rate,
+            role,
+            transpose: 0,
+            cutoff: exponential(CUTOFFS, 0.5),
+            resonance: linear(RESONANCES, 0.5),
+            mutation: 1.0,
+            freeze: false,
+        }
+    }
+
+    pub fn apply(&mut self, control: Control) -> Result<Command, libpoly::Error> {
+        let command = match control {
+            Control::Tempo(bpm) => Command::Tempo(bpm),
+            Control::Cutoff(cutoff) => {
+                self.cutoff = cutoff;
+                Command::Filter(self.cutoff, self.resonance)
+            }
+            Control::Resonance(resonance) => {
+                self.resonance = resonance;
+                Command::Filter(self.cutoff, self.resonance)
+            }
+            Control::Mutation(mutation) => {
+                self.mutation = mutation;
+                Command::Mutation(mutation)
+            }
+            Control::Transpose(transpose) => {
+                let semitones = transpose - self.transpose;
+                self.transpose = transpose;
+                Command::Transpose(semitones)
+            }
+            Control::Reseed(seed) => {
+                let mut poly = Box::new(Poly::new(seed, self.sample_rate, &PolyConfig::default())?);
+                poly.set_mutation(self.mutation)?;
+                poly.set_locked(self.freeze);
+                poly.set_role(self.role);
+                Command::Filter(self.cutoff, self.resonance).apply(&mut poly)?;
+                self.transpose = 0;
+                Command::Reseed(poly)
+            }
+            Control::Freeze(freeze) => {
+                self.freeze = freeze;
+                Command::Freeze(freeze)
+            }
+            Control::Transport(transport) => Command::Transport(transport),
+        };
+        Ok(command)
+    }
+}
+
+pub fn connect(
+    port: &str,
+    mut performance: Performance,
+    commands: Sender<Command>,
+) -> Result<MidiInputConnection<()>, Error> {
+    let mut input = MidiInput::new(CLIENT)?;
+    input.ignore(Ignore::SysexAndActiveSense);
+
+    let callback = move |_: u64, message: &[u8], _: &mut ()| {
+        if let Some(control) = Control::parse(message) {
+            match performance.apply(control) {
+                Ok(command) => {
+                    commands.send(command).ok();
+                }
+                Err(error) => eprintln!("ignored {:?}: {:?}", control, error),
+            }
+        }
+    };
+
+    if port == VIRTUAL {
+        return Ok(input.create_virtual(CLIENT, callback, ())?);
+    }
+
+    let port = find(&input, port)?;
+    Ok(input.connect(&port, CLIENT, callback, ())?)
+}
+
+pub struct Output(MidiOutputConnection);
+
+impl Output {
+    pub fn connect(port: &str) -> Result<Self, Error> {
+        let output = MidiOutput::new(CLIENT)?;
+        if port == VIRTUAL {
+            return Ok(Output(output.create_virtual(CLIENT)?));
+        }
+
+        let port = find(&output, port)?;
+        Ok(Output(output.connect(&port, CLIENT)?))
+    }
+
+    pub fn send(&mut self, transport: Transport) {
+        let mut bytes = [0; 3];
+        if let Ok(length) = transport.encode(&mut bytes) {
+            self.0.send(&bytes[..length]).ok();
+        }
+    }
+}
+
+fn find<T: MidiIO>(io: &T, port: &str) -> Result<T::Port, Error> {
+    io.ports()
+        .into_iter()
+        .find(|candidate| {
+            io.port_name(candidate)
+                .map_or(false, |name| name.contains(port))
+        })
+        .ok_or(Error::MidiPort)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(poly: &mut Poly) -> Vec<[f32; 2]> {
+        let mut frames = vec![[0.0; 2]; 44100];
+        poly.render(&mut frames);
+        frames
+    }
+
+    #[test]
+    fn controllers() {
+        assert_eq!(
+            Some(Control::Tempo(40.0)),
+            Control::parse(&[0xB0, TEMPO, 0])
+        );
+        assert_eq!(
+            Some(Control::Tempo(200.0)),
+            Control::parse(&[0xB3, TEMPO, 127])
+        );
+        assert_eq!(
+            Some(Control::Cutoff(40.0)),
+            Control::parse(&[0xB0, CUTOFF, 0])
+        );
+        assert_eq!(
+            Some(Control::Resonance(0.95)),
+            Control::parse(&[0xB0, RESONANCE, 127])
+        );
+        assert_eq!(
+            Some(Control::Mutation(0.0625)),
+            Control::parse(&[0xB0, MUTATION, 0])
+        );
+        assert_eq!(
+            Some(Control::Mutation(16.0)),
+            Control::parse(&[0xB0, MUTATION, 127])
+        );
+        assert_eq!(
+            Some(Control::Freeze(true)),
+            Control::parse(&[0xB0, FREEZE, 64])
+        );
+        assert_eq!(
+            Some(Control::Freeze(false)),
+            Control::parse(&[0xB0, FREEZE, 63])
+        );
+        assert_eq!(None, Control::parse(&[0xB0, 7, 100]));
+    }
+
+    #[test]
+    fn notes() {
+        assert_eq!(
+            Some(Control::Transpose(0)),
+            Control::parse(&[0x90, 60, 100])
+        );
+        assert_eq!(
+            Some(Control::Transpose(-12)),
+            Control::parse(&[0x95, 48, 1])
+        );
+        assert_eq!(None, Control::parse(&[0x90, 62, 0]));
+        assert_eq!(None, Control::parse(&[0x80, 62, 64]));
+    }
+
+    #[test]
+    fn programs() {
+        assert_eq!(Some(Control::Reseed(5)), Control::parse(&[0xC0, 5]));
+        assert_eq!(Some(Control::Reseed(127)), Control::parse(&[0xCF, 127]));
+        assert_eq!(
+            Some(Control::Transport(Transport::Start)),
+            Control::parse(&[0xFA])
+        );
+    }
+
+    #[test]
+    fn transpose() {
+        let mut performance = Performance::new(44100, Role::Internal);
+        assert!(matches!(
+            performance.apply(Control::Transpose(2)),
+            Ok(Command::Transpose(2))
+        ));
+        assert!(matches!(
+            performance.apply(Control::Transpose(-3)),
+            Ok(Command::Transpose(-5))
+        ));
+    }
+
+    #[test]
+    fn filter() {
+        let mut performance = Performance::new(44100, Role::Internal);
+        let resonance = linear(RESONANCES, 0.5);
+        assert!(matches!(
+            performance.apply(Control::Cutoff(1000.0)),
+            Ok(Command::Filter(cutoff, q)) if cutoff == 1000.0 && q == resonance
+        ));
+        assert!(matches!(
+            performance.apply(Control::Resonance(0.2)),
+            Ok(Command::Filter(cutoff, q)) if cutoff == 1000.0 && q == 0.2
+        ));
+    }
+
+    #[test]
+    fn reseed() {
+        let mut performance = Performance::new(44100, Role::Internal);
+        performance.apply(Control::Transpose(5)).unwrap();
+        performance.apply(Control::Cutoff(1000.0)).unwrap();
+        performance.apply(Control::Freeze(true)).unwrap();
+
+        let mut reseeded = match performance.apply(Control::Reseed(7)).unwrap() {
+            Command::Reseed(poly) => poly,
+            _ => panic!("expected a reseed"),
+        };
+        assert!(matches!(
+            performance.apply(Control::Transpose(5)),
+            Ok(Command::Transpose(5))
+        ));
+
+        let mut expected = Box::new(Poly::new(7, 44100, &PolyConfig::default()).unwrap());
+        let mut unfiltered = Box::new(Poly::new(7, 44100, &PolyConfig::default()).unwrap());
+        Command::Filter(1000.0, linear(RESONANCES, 0.5))
+            .apply(&mut expected)
+            .unwrap();
+        Command::Freeze(true).apply(&mut expected).unwrap();
+        let frames = render(&mut reseeded);
+        assert_eq!(render(&mut expected), frames);
+        assert_ne!(render(&mut unfiltered), frames);
+    }
+
+    #[test]
+    fn swap() {
+        let mut poly = Box::new(Poly::new(0, 44100, &PolyConfig::default()).unwrap());
+        render(&mut poly);
+
+        let reseeded = Box::new(Poly::new(1, 44100, &PolyConfig::default()).unwrap());
+        let retired = Command::Reseed(reseeded).apply(&mut poly).unwrap().unwrap();
+        assert_eq!(44100, retired.frame());
+        assert_eq!(0, poly.frame());
+        assert!(Command::Tempo(0.0).apply(&mut poly).is_err());
+    }
+}
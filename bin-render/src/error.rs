@@ -0,0 +1,30 @@
+#[derive(Debug)]
+pub enum Error {
+    Poly(libpoly::Error),
+    Usage,
+    Seed,
+    Duration,
+    SampleRate,
+    Format,
+    Tuning,
+    Wav(hound::Error),
+    Io(std::io::Error),
+}
+
+impl From<libpoly::Error> for Error {
+    fn from(error: libpoly::Error) -> Self {
+        Error::Poly(error)
+    }
+}
+
+impl From<hound::Error> for Error {
+    fn from(error: hound::Error) -> Self {
+        Error::Wav(error)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
@@ -0,0 +1,178 @@
+mod error;
+mod midi;
+
+use std::env;
+use std::fs;
+use std::io::{Seek, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+
+use libpoly::{Poly, PolyConfig, Scala, Snapshot, Tuning};
+
+use error::Error;
+
+const BUFFER: usize = 1024;
+const CHANNELS: u16 = 2;
+const DURATION: f32 = 60.0;
+const SAMPLE_RATE: u32 = 44100;
+
+pub fn main() -> Result<(), Error> {
+    pretty_env_logger::init();
+
+    let mut args = env::args().skip(1);
+
+    let path = args.next().ok_or(Error::Usage)?;
+
+    let seed = args.next();
+
+    let duration = match args.next() {
+        Some(arg) => arg.parse::<f32>().map_err(|_| Error::Duration)?,
+        None => DURATION,
+    };
+
+    let sample_rate = match args.next() {
+        Some(arg) => arg.parse::<u32>().map_err(|_| Error::SampleRate)?,
+        None => SAMPLE_RATE,
+    };
+
+    let format = match args.next() {
+        Some(arg) => Format::parse(&arg)?,
+        None => Format::I16,
+    };
+
+    let tuning = match args.next() {
+        Some(arg) => Some(tuning(&arg)?),
+        None => None,
+    };
+
+    let config = PolyConfig::default();
+    let mut poly = match seed {
+        Some(arg) => match arg.parse::<u64>() {
+            Ok(seed) => Poly::new(seed, sample_rate, &config)?,
+            Err(_) => restore(&arg, sample_rate, &config)?,
+        },
+        None => Poly::new(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|_| Error::Seed)?
+                .as_secs(),
+            sample_rate,
+            &config,
+        )?,
+    };
+    if let Some(tuning) = tuning {
+        poly.set_tuning(tuning);
+    }
+
+    if path.ends_with(".mid") {
+        return midi::render(&mut poly, sample_rate, duration, path);
+    }
+
+    let mut writer = WavWriter::create(path, format.spec(sample_rate))?;
+    render(&mut poly, sample_rate, duration, format, &mut writer)?;
+    writer.finalize()?;
+
+    Ok(())
+}
+
+fn restore(path: &str, sample_rate: u32, config: &PolyConfig) -> Result<Poly, Error> {
+    let bytes = fs::read(path)?;
+    let snapshot = Snapshot::decode(&bytes)?;
+
+    let mut poly = Poly::new(0, sample_rate, config)?;
+    poly.restore(&snapshot)?;
+    Ok(poly)
+}
+
+fn tuning(arg: &str) -> Result<Tuning, Error> {
+    if arg == "just" {
+        return Ok(Tuning::Just);
+    }
+
+    if let Some(divisions) = arg.strip_suffix("edo") {
+        return match divisions.parse::<u32>() {
+            Ok(divisions) if divisions > 0 => Ok(Tuning::Equal(divisions)),
+            _ => Err(Error::Tuning),
+        };
+    }
+
+    let (scl, kbm) = match arg.split_once(':') {
+        Some((scl, kbm)) => (scl, Some(fs::read_to_string(kbm)?)),
+        None => (arg, None),
+    };
+    let scala = Scala::parse(&fs::read_to_string(scl)?, kbm.as_deref())?;
+
+    Ok(Tuning::Scala(scala))
+}
+
+pub fn render<W>(
+    poly: &mut Poly,
+    sample_rate: u32,
+    duration: f32,
+    format: Format,
+    writer: &mut WavWriter<W>,
+) -> Result<(), Error>
+where
+    W: Write + Seek,
+{
+    let channels = CHANNELS as usize;
+    let mut buffer = [0.0; BUFFER];
+    let mut remaining = (duration * sample_rate as f32) as usize * channels;
+    while remaining > 0 {
+        let length = remaining.min(BUFFER);
+        poly.render_interleaved(&mut buffer[..length], channels);
+        for sample in &buffer[..length] {
+            format.write(writer, *sample)?;
+        }
+        remaining -= length;
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    I16,
+    I24,
+    F32,
+}
+
+impl Format {
+    fn parse(arg: &str) -> Result<Self, Error> {
+        match arg {
+            "i16" => Ok(Format::I16),
+            "i24" => Ok(Format::I24),
+            "f32" => Ok(Format::F32),
+            _ => Err(Error::Format),
+        }
+    }
+
+    fn spec(&self, sample_rate: u32) -> WavSpec {
+        let (bits_per_sample, sample_format) = match self {
+            Format::I16 => (16, SampleFormat::Int),
+            Format::I24 => (24, SampleFormat::Int),
+            Format::F32 => (32, SampleFormat::Float),
+        };
+
+        WavSpec {
+            channels: CHANNELS,
+            sample_rate,
+            bits_per_sample,
+            sample_format,
+        }
+    }
+
+    fn write<W>(&self, writer: &mut WavWriter<W>, sample: f32) -> Result<(), Error>
+    where
+        W: Write + Seek,
+    {
+        let sample = sample.clamp(-1.0, 1.0);
+        match self {
+            Format::I16 => writer.write_sample((sample * i16::MAX as f32) as i16)?,
+            Format::I24 => writer.write_sample((sample * 8_388_607.0) as i32)?,
+            Format::F32 => writer.write_sample(sample)?,
+        }
+        Ok(())
+    }
+}
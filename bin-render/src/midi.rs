@@ -0,0 +1,110 @@
+use std::path::Path;
+
+use midly::num::{u15, u24, u28, u4, u7};
+use midly::{Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind};
+
+use libpoly::{Event, Frame, Poly};
+
+use crate::error::Error;
+
+const BUFFER: usize = 1024;
+const PPQ: u16 = 480;
+
+pub fn render<P>(poly: &mut Poly, sample_rate: u32, duration: f32, path: P) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+{
+    let rate = |bpm: f32| PPQ as f64 * bpm as f64 / (60.0 * sample_rate as f64);
+    let mut events = Vec::new();
+    let mut tempos = vec![(poly.frame(), 0.0, poly.bpm())];
+    let mut buffer: [Frame; BUFFER] = [[0.0; 2]; BUFFER];
+    let mut remaining = (duration * sample_rate as f32) as usize;
+    while remaining > 0 {
+        let length = remaining.min(BUFFER);
+        poly.render_events(&mut buffer[..length], |event| events.push(event));
+        remaining -= length;
+
+        let (frame, tick, bpm) = tempos[tempos.len() - 1];
+        if poly.bpm() != bpm {
+            let tick = tick + (poly.frame() - frame) as f64 * rate(bpm);
+            tempos.push((poly.frame(), tick, poly.bpm()));
+        }
+    }
+
+    let ticks = |frame: u64| -> u64 {
+        let index = tempos.partition_point(|(start, _, _)| *start <= frame);
+        let (start, tick, bpm) = tempos[index.max(1) - 1];
+        (tick + frame.saturating_sub(start) as f64 * rate(bpm)).round() as u64
+    };
+
+    let mut smf = Smf::new(Header::new(
+        Format::Parallel,
+        Timing::Metrical(u15::new(PPQ)),
+    ));
+    let mut previous = 0;
+    let mut tempo = tempos
+        .iter()
+        .map(|(frame, _, bpm)| {
+            let tick = ticks(*frame);
+            let delta = tick - previous;
+            previous = tick;
+            meta(
+                delta as u32,
+                MetaMessage::Tempo(u24::new((60_000_000.0 / bpm) as u32)),
+            )
+        })
+        .collect::<Vec<TrackEvent>>();
+    tempo.push(meta(0, MetaMessage::EndOfTrack));
+    smf.tracks.push(tempo);
+
+    let tracks = events
+        .iter()
+        .map(|event| event.track + 1)
+        .max()
+        .unwrap_or(0);
+    for track in 0..tracks {
+        let mut messages = events
+            .iter()
+            .filter(|event| event.track == track)
+            .flat_map(|event: &Event| {
+                let (key, vel) = (u7::new(event.note), u7::new(event.velocity));
+                [
+                    (ticks(event.start), 1, MidiMessage::NoteOn { key, vel }),
+                    (
+                        ticks(event.start + event.length as u64),
+                        0,
+                        MidiMessage::NoteOff { key, vel },
+                    ),
+                ]
+            })
+            .collect::<Vec<(u64, u8, MidiMessage)>>();
+        messages.sort_by_key(|(tick, order, _)| (*tick, *order));
+
+        let channel = u4::new(track as u8);
+        let mut previous = 0;
+        let mut track = messages
+            .into_iter()
+            .map(|(tick, _, message)| {
+                let delta = tick - previous;
+                previous = tick;
+                TrackEvent {
+                    delta: u28::new(delta as u32),
+                    kind: TrackEventKind::Midi { channel, message },
+                }
+            })
+            .collect::<Vec<TrackEvent>>();
+        track.push(meta(0, MetaMessage::EndOfTrack));
+        smf.tracks.push(track);
+    }
+
+    smf.save(path)?;
+
+    Ok(())
+}
+
+fn meta(delta: u32, message: MetaMessage) -> TrackEvent {
+    TrackEvent {
+        delta: u28::new(delta),
+        kind: TrackEventKind::Meta(message),
+    }
+}
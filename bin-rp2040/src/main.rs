@@ -5,22 +5,28 @@ use defmt::{debug, panic};
 use defmt_rtt as _;
 use panic_probe as _;
 
-use libpoly::Poly;
+use libpoly::{Lines, Poly, PolyConfig};
 
 #[link_section = ".boot2"]
 #[used]
 pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_W25Q080;
 
+const BUFFER: usize = 64;
+
 #[cortex_m_rt::entry]
 fn main() -> ! {
+    static mut LINES: Lines = Lines::new();
+
     debug!("Program start");
 
-    let mut poly = match Poly::new(0, 44100) {
+    let mut poly = match Poly::with_lines(0, 44100, &PolyConfig::default(), LINES) {
         Err(_) => panic!("Init failed"),
         Ok(p) => p,
     };
 
+    let mut buffer = [[0.0; 2]; BUFFER];
     loop {
-        debug!("{}", poly.next());
+        poly.render(&mut buffer);
+        debug!("{}", buffer);
     }
 }
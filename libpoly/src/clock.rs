@@ -1,3 +1,6 @@
+use crate::snapshot::ClockState;
+
+#[derive(Clone)]
 pub struct Clock {
     tick: usize,
     deadline: usize,
@@ -13,14 +16,45 @@ impl Clock {
     }
 
     pub fn tick(&mut self) -> bool {
-        self.tick = self.tick.wrapping_add(1);
+        self.advance(1)
+    }
+
+    pub fn advance(&mut self, ticks: usize) -> bool {
+        self.tick = self.tick.wrapping_add(ticks);
         self.ready()
     }
 
+    pub fn remaining(&self) -> usize {
+        self.deadline.saturating_sub(self.tick).max(1)
+    }
+
     pub fn reset(&mut self, deadline: usize) {
         self.tick = 0;
         self.deadline = deadline;
     }
+
+    pub fn scale(&mut self, factor: f32) {
+        let remaining = self.deadline.saturating_sub(self.tick);
+        self.deadline = self
+            .tick
+            .saturating_add((remaining as f32 * factor) as usize);
+    }
+
+    pub fn snapshot(&self) -> ClockState {
+        ClockState {
+            tick: self.tick,
+            deadline: self.deadline,
+        }
+    }
+
+    pub fn restore(&mut self, state: &ClockState) {
+        self.tick = state.tick;
+        self.deadline = state.deadline;
+    }
+}
+
+pub fn interval(samples: usize, rate: f32) -> usize {
+    (samples as f32 / rate) as usize
 }
 
 impl Default for Clock {
@@ -60,6 +94,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn advance() {
+        let mut clock = Clock::deadline(10);
+        assert!(!clock.advance(9));
+        assert!(clock.advance(1));
+        assert_eq!(10, clock.tick);
+    }
+
+    #[test]
+    fn remaining() {
+        let mut clock = Clock::deadline(5);
+        assert_eq!(5, clock.remaining());
+
+        clock.advance(3);
+        assert_eq!(2, clock.remaining());
+
+        clock.advance(10);
+        assert_eq!(1, clock.remaining());
+    }
+
     #[test]
     fn reset() {
         let mut clock = Clock::deadline(10);
@@ -71,4 +125,26 @@ mod tests {
         assert_eq!(0, clock.tick);
         assert_eq!(5, clock.deadline);
     }
+
+    #[test]
+    fn scale() {
+        let mut clock = Clock::deadline(10);
+        clock.advance(4);
+        clock.scale(0.5);
+        assert_eq!(3, clock.remaining());
+
+        clock.scale(4.0);
+        assert_eq!(12, clock.remaining());
+
+        let mut clock = Clock::deadline(usize::MAX);
+        clock.advance(1);
+        clock.scale(2.0);
+        assert_eq!(usize::MAX, clock.deadline);
+    }
+
+    #[test]
+    fn interval() {
+        assert_eq!(50, super::interval(100, 2.0));
+        assert_eq!(400, super::interval(100, 0.25));
+    }
 }
@@ -0,0 +1,215 @@
+use core::ops::RangeInclusive;
+
+use crate::constants::{
+    ATTACK, CUTOFF, DECAY, DELAY, DELAY_LINE, LENGTH, METRES, OPERATORS, PPQ, Q, RELEASE,
+    RESPONSES, SEQUENCE_MUTATE, SUSTAIN, SYNTH_MUTATE, TEMPOS,
+};
+use crate::error::Error;
+use crate::synth::{Response, Signal};
+
+#[derive(Clone, Debug)]
+pub struct PolyConfig {
+    pub(crate) tempos: RangeInclusive<f32>,
+    pub(crate) metres: &'static [usize],
+    pub(crate) attack: RangeInclusive<f32>,
+    pub(crate) decay: RangeInclusive<f32>,
+    pub(crate) sustain: RangeInclusive<f32>,
+    pub(crate) release: RangeInclusive<f32>,
+    pub(crate) cutoff: RangeInclusive<f32>,
+    pub(crate) q: RangeInclusive<f32>,
+    pub(crate) responses: &'static [Response],
+    pub(crate) operators: &'static [(Signal, RangeInclusive<f32>)],
+    pub(crate) sequence_mutate: RangeInclusive<usize>,
+    pub(crate) synth_mutate: RangeInclusive<usize>,
+}
+
+impl PolyConfig {
+    pub fn tempos(mut self, tempos: RangeInclusive<f32>) -> Self {
+        self.tempos = tempos;
+        self
+    }
+
+    pub fn metres(mut self, metres: &'static [usize]) -> Self {
+        self.metres = metres;
+        self
+    }
+
+    pub fn envelope(
+        mut self,
+        attack: RangeInclusive<f32>,
+        decay: RangeInclusive<f32>,
+        sustain: RangeInclusive<f32>,
+        release: RangeInclusive<f32>,
+    ) -> Self {
+        self.attack = attack;
+        self.decay = decay;
+        self.sustain = sustain;
+        self.release = release;
+        self
+    }
+
+    pub fn filter(mut self, cutoff: RangeInclusive<f32>, q: RangeInclusive<f32>) -> Self {
+        self.cutoff = cutoff;
+        self.q = q;
+        self
+    }
+
+    pub fn responses(mut self, responses: &'static [Response]) -> Self {
+        self.responses = responses;
+        self
+    }
+
+    pub fn operators(mut self, operators: &'static [(Signal, RangeInclusive<f32>)]) -> Self {
+        self.operators = operators;
+        self
+    }
+
+    pub fn mutation(
+        mut self,
+        sequence: RangeInclusive<usize>,
+        synth: RangeInclusive<usize>,
+    ) -> Self {
+        self.sequence_mutate = sequence;
+        self.synth_mutate = synth;
+        self
+    }
+
+    pub fn validate(&self, sample_rate: u32) -> Result<(), Error> {
+        positive(&self.tempos, f32::MAX, "tempos must be positive")?;
+        let slowest = sample_rate as f32 * 60.0 / self.tempos.start();
+        if DELAY[DELAY.len() - 1] as f32 * slowest / PPQ as f32 >= (DELAY_LINE - 1) as f32 {
+            return Err(Error::Config("delays must fit in the delay line"));
+        }
+        if self.metres.is_empty() || self.metres.iter().any(|&m| m == 0 || m > LENGTH) {
+            return Err(Error::Config("metres must fit in a sequence"));
+        }
+
+        within(&self.attack, "attack must be within 0 and 1")?;
+        within(&self.decay, "decay must be within 0 and 1")?;
+        within(&self.sustain, "sustain must be within 0 and 1")?;
+        within(&self.release, "release must be within 0 and 1")?;
+        if self.attack.end() + self.decay.end() + self.release.end() > 1.0 {
+            return Err(Error::Config("envelopes must fit in a note"));
+        }
+
+        let nyquist = sample_rate as f32 / 2.0;
+        positive(&self.cutoff, nyquist, "cutoff must be below nyquist")?;
+        within(&self.q, "q must be within 0 and 1")?;
+        if self.responses.is_empty() {
+            return Err(Error::Config("responses must not be empty"));
+        }
+
+        if self.operators.is_empty() {
+            return Err(Error::Config("operators must not be empty"));
+        }
+        for (_, ratios) in self.operators {
+            positive(ratios, f32::MAX, "operator ratios must be positive")?;
+        }
+
+        if self.sequence_mutate.is_empty() || *self.sequence_mutate.start() == 0 {
+            return Err(Error::Config("sequence mutation must be positive"));
+        }
+        if self.synth_mutate.is_empty() || *self.synth_mutate.start() == 0 {
+            return Err(Error::Config("synth mutation must be positive"));
+        }
+        Ok(())
+    }
+}
+
+fn within(range: &RangeInclusive<f32>, message: &'static str) -> Result<(), Error> {
+    if *range.start() >= 0.0 && *range.end() <= 1.0 && !range.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Config(message))
+    }
+}
+
+fn positive(range: &RangeInclusive<f32>, limit: f32, message: &'static str) -> Result<(), Error> {
+    if *range.start() > 0.0 && *range.end() < limit && !range.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Config(message))
+    }
+}
+
+impl Default for PolyConfig {
+    fn default() -> Self {
+        PolyConfig {
+            tempos: TEMPOS,
+            metres: &METRES,
+            attack: ATTACK,
+            decay: DECAY,
+            sustain: SUSTAIN,
+            release: RELEASE,
+            cutoff: CUTOFF,
+            q: Q,
+            responses: &RESPONSES,
+            operators: &OPERATORS,
+            sequence_mutate: SEQUENCE_MUTATE,
+            synth_mutate: SYNTH_MUTATE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default() {
+        assert!(PolyConfig::default().validate(44100).is_ok());
+        assert!(PolyConfig::default().validate(48000).is_ok());
+    }
+
+    #[test]
+    fn builder() {
+        let config = PolyConfig::default()
+            .tempos(64.0..=70.0)
+            .metres(&[4])
+            .envelope(0.1..=0.2, 0.1..=0.1, 0.5..=0.5, 0.1..=0.2)
+            .filter(200.0..=300.0, 0.0..=0.1)
+            .responses(&[Response::Notch])
+            .operators(&[(Signal::Sine, 1.0..=1.0)])
+            .mutation(1..=10, 100..=1000);
+        assert!(config.validate(44100).is_ok());
+        assert_eq!(64.0..=70.0, config.tempos);
+        assert_eq!(&[4], config.metres);
+        assert_eq!(&[Response::Notch], config.responses);
+        assert_eq!(100..=1000, config.synth_mutate);
+    }
+
+    #[test]
+    fn validate() {
+        let config = PolyConfig::default;
+        let errors = [
+            config().tempos(0.0..=100.0),
+            config().tempos(120.0..=80.0),
+            config().tempos(80.0..=f32::INFINITY),
+            config().tempos(20.0..=120.0),
+            config().metres(&[]),
+            config().metres(&[4, 9]),
+            config().envelope(0.5..=0.6, DECAY, SUSTAIN, 0.3..=0.4),
+            config().envelope(ATTACK, DECAY, 0.5..=1.5, RELEASE),
+            config().filter(400.0..=30_000.0, Q),
+            config().filter(CUTOFF, -0.1..=0.5),
+            config().responses(&[]),
+            config().operators(&[]),
+            config().operators(&[(Signal::Saw, 0.0..=1.0)]),
+            config().mutation(0..=10, SYNTH_MUTATE),
+            #[allow(clippy::reversed_empty_ranges)]
+            config().mutation(SEQUENCE_MUTATE, 10..=1),
+        ];
+        for config in errors {
+            assert!(matches!(config.validate(44100), Err(Error::Config(_))));
+        }
+
+        assert!(matches!(
+            config().filter(400.0..=30_000.0, Q).validate(44100),
+            Err(Error::Config("cutoff must be below nyquist"))
+        ));
+        assert!(matches!(
+            config().validate(96000),
+            Err(Error::Config("delays must fit in the delay line"))
+        ));
+    }
+}
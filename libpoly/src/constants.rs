@@ -1,14 +1,63 @@
 use core::ops::RangeInclusive;
 
-use crate::synth::Signal;
+use crate::synth::{Algorithm, Destination, Oscillator, Response, Signal, Source, Steal};
+use crate::theory::{Mode, Progression};
+use crate::track::Part;
+
+pub const BLOCK: usize = 64;
+pub const CONTROL: usize = 32;
+pub const SMOOTH: usize = 32;
 
 pub const SEQUENCE_MUTATE: RangeInclusive<usize> = 441_000..=882_000;
 pub const SYNTH_MUTATE: RangeInclusive<usize> = 2_205_000..=4_410_000;
 
+pub const TRACKS: [Part; 3] = [
+    Part {
+        octaves: -2..=-1,
+        stretch: 2,
+        level: 0.6,
+    },
+    Part {
+        octaves: 0..=1,
+        stretch: 1,
+        level: 0.4,
+    },
+    Part {
+        octaves: -1..=0,
+        stretch: 4,
+        level: 0.3,
+    },
+];
+
 pub const TEMPOS: RangeInclusive<f32> = 80.0..=120.0;
+pub const TEMPO_STEP: usize = 4_410;
+pub const TEMPO_RAMP: f32 = 0.02;
+pub const DRIFT: RangeInclusive<usize> = 2_646_000..=5_292_000;
+pub const MODULATE: RangeInclusive<usize> = 5_292_000..=10_584_000;
+pub const METRE: RangeInclusive<usize> = 7_938_000..=15_876_000;
 pub const METRES: [usize; 5] = [3, 4, 5, 7, 8];
+pub const PULSES: usize = 24;
+pub const FOLLOW: f64 = 0.1;
+pub const FOLLOW_TEMPOS: RangeInclusive<f32> = 20.0..=300.0;
 pub const LENGTH: usize = 8;
 
+pub const PPQ: usize = 96;
+pub const DIVISIONS: [usize; 7] = [24, 32, 48, 64, 96, 144, 192];
+pub const SWING: RangeInclusive<f32> = 0.0..=0.33;
+pub const REST: f64 = 0.15;
+pub const ORDER: usize = 2;
+pub const DEGREES: usize = 7;
+pub const MOTION: [f32; 8] = [1.0, 8.0, 4.0, 2.0, 1.0, 0.5, 0.25, 0.5];
+pub const LEAP: i32 = 3;
+pub const RECOVERY: f32 = 3.0;
+pub const GRAVITY: f32 = 1.25;
+pub const TIE: f64 = 0.1;
+
+pub const OSCILLATORS: [Oscillator; 3] = [
+    Oscillator::PolyBlep,
+    Oscillator::PolyBlep,
+    Oscillator::Naive,
+];
 pub const OPERATORS: [(Signal, RangeInclusive<f32>); 5] = [
     (Signal::Square, 1.0..=1.0),
     (Signal::Saw, 1.0..=1.0),
@@ -17,224 +66,208 @@ pub const OPERATORS: [(Signal, RangeInclusive<f32>); 5] = [
     (Signal::Square, 0.2..=0.4),
 ];
 
+pub const ALGORITHMS: [Algorithm; 5] = [
+    Algorithm {
+        name: "Stack",
+        modulators: [[false, true, false], [false, false, true], [false; 3]],
+        carriers: [true, false, false],
+        feedback: 2,
+    },
+    Algorithm {
+        name: "Pair",
+        modulators: [[false, true, true], [false; 3], [false; 3]],
+        carriers: [true, false, false],
+        feedback: 2,
+    },
+    Algorithm {
+        name: "Branch",
+        modulators: [[false, false, true], [false, false, true], [false; 3]],
+        carriers: [true, true, false],
+        feedback: 2,
+    },
+    Algorithm {
+        name: "Split",
+        modulators: [[false, true, false], [false; 3], [false; 3]],
+        carriers: [true, false, true],
+        feedback: 1,
+    },
+    Algorithm {
+        name: "Additive",
+        modulators: [[false; 3]; 3],
+        carriers: [true, true, true],
+        feedback: 0,
+    },
+];
+pub const INDEX: RangeInclusive<f32> = 0.5..=4.0;
+pub const FEEDBACK: RangeInclusive<f32> = 0.0..=0.8;
+
+pub const VOICES: usize = 8;
+pub const POLYPHONY: usize = 4;
+pub const STEAL: Steal = Steal::Oldest;
+
 pub const ATTACK: RangeInclusive<f32> = 0.01..=0.6;
 pub const DECAY: RangeInclusive<f32> = 0.01..=0.1;
 pub const SUSTAIN: RangeInclusive<f32> = 0.4..=0.95;
 pub const RELEASE: RangeInclusive<f32> = 0.01..=0.05;
+pub const CURVE: RangeInclusive<f32> = 0.0..=4.0;
+pub const STAGE: f32 = 10.0;
+
+pub const LFOS: usize = 2;
+pub const LFO_RATE: RangeInclusive<f32> = 0.05..=6.0;
+pub const LFO_SYNC: [usize; 6] = [24, 48, 96, 192, 384, 768];
+pub const LFO_SYNCED: f64 = 0.5;
+pub const WALK: f32 = 0.5;
+pub const ROUTES: usize = 4;
+pub const SOURCES: [Source; 5] = [
+    Source::Lfo(0),
+    Source::Lfo(1),
+    Source::Envelope(0),
+    Source::Envelope(1),
+    Source::Envelope(2),
+];
+pub const DESTINATIONS: [(Destination, RangeInclusive<f32>); 10] = [
+    (Destination::Ratio(0), -0.02..=0.02),
+    (Destination::Ratio(1), -0.02..=0.02),
+    (Destination::Ratio(2), -0.02..=0.02),
+    (Destination::Level(0), -0.5..=0.5),
+    (Destination::Level(1), -0.5..=0.5),
+    (Destination::Level(2), -0.5..=0.5),
+    (Destination::Cutoff, -1.5..=1.5),
+    (Destination::Q, -0.2..=0.2),
+    (Destination::Pan, -0.5..=0.5),
+    (Destination::Amplitude, -0.3..=0.3),
+];
+
+pub const VELOCITY: RangeInclusive<f32> = 0.6..=1.0;
+pub const PAN: RangeInclusive<f32> = -0.8..=0.8;
+pub const AUTOPAN: f64 = 0.5;
+pub const AUTOPAN_DEPTH: RangeInclusive<f32> = 0.2..=0.6;
+pub const AUTOPAN_RATE: RangeInclusive<f32> = 0.02..=0.2;
 
 pub const CUTOFF: RangeInclusive<f32> = 400.0..=600.0;
 pub const Q: RangeInclusive<f32> = 0.2..=0.5;
+pub const DAMPING: f32 = 0.1;
+pub const FILTER_DEPTH: RangeInclusive<f32> = 0.0..=3.0;
+pub const TRACKING: RangeInclusive<f32> = 0.0..=1.0;
+pub const SENSITIVITY: RangeInclusive<f32> = 0.0..=1.0;
+pub const KEY: f32 = 261.63;
+pub const FLOOR: f32 = 20.0;
+pub const CEILING: f32 = 0.45;
+pub const RESPONSES: [Response; 6] = [
+    Response::Ladder,
+    Response::Ladder,
+    Response::Lowpass,
+    Response::Bandpass,
+    Response::Highpass,
+    Response::Notch,
+];
+
+pub const REVERB: [usize; 4] = [1087, 1283, 1447, 1663];
+pub const REVERB_LINE: usize = 2048;
+pub const DIFFUSERS: [usize; 2] = [142, 379];
+pub const DIFFUSER_LINE: usize = 512;
+pub const DIFFUSION: f32 = 0.6;
+pub const ROOM: RangeInclusive<f32> = 0.75..=0.9;
+pub const DAMP: RangeInclusive<f32> = 0.2..=0.6;
+pub const REVERB_MIX: RangeInclusive<f32> = 0.1..=0.3;
+pub const DELAY: [usize; 5] = [24, 32, 36, 48, 72];
+pub const DELAY_LINE: usize = 32768;
+pub const DELAY_GLIDE: f32 = 0.001;
+pub const DELAY_FEEDBACK: RangeInclusive<f32> = 0.2..=0.5;
+pub const DELAY_MIX: RangeInclusive<f32> = 0.1..=0.25;
 
-pub type Scale = [f32; 8];
-pub const SCALES: [Scale; 21] = [
-    [
-        739.9800001503532,
-        678.3149999421656,
-        616.6500001254908,
-        924.9750002601583,
-        462.4875001300791,
-        847.8937499939062,
-        1017.4724999923628,
-        508.736249996181,
-    ],
-    [
-        832.4774998177487,
-        739.9800001503532,
-        678.3149999421656,
-        554.9850001559179,
-        1109.970000311835,
-        1017.4724999923628,
-        763.1043750536072,
-        508.736249996181,
-    ],
-    [
-        739.9800001503532,
-        485.61187500483805,
-        647.482499956106,
-        616.6500001254908,
-        554.9850001559179,
-        462.4875001300791,
-        693.731249848345,
-        539.5687499635937,
-    ],
-    [
-        739.9800001503532,
-        647.482499956106,
-        616.6500001254908,
-        462.4875001300791,
-        501.02812487931885,
-        601.2337498549912,
-        539.5687499635937,
-        701.4393749913753,
-    ],
-    [
-        832.4774998177487,
-        801.6450002073708,
-        450.92531242630474,
-        739.9800001503532,
-        901.8506248526095,
-        554.9850001559179,
-        1109.970000311835,
-        601.2337498549912,
-    ],
-    [
-        801.6450002073708,
-        739.9800001503532,
-        616.6500001254908,
-        1002.0562497586384,
-        462.4875001300791,
-        501.02812487931885,
-        601.2337498549912,
-        924.9750002601583,
-    ],
-    [
-        739.9800001503532,
-        581.4128572960421,
-        654.0894641819485,
-        687.1242857347563,
-        472.39794634311426,
-        601.2337498549912,
-        475.70142859618926,
-        508.736249996181,
-    ],
-    [
-        450.92531242630474,
-        739.9800001503532,
-        616.6500001254908,
-        554.9850001559179,
-        462.4875001300791,
-        501.02812487931885,
-        693.731249848345,
-        601.2337498549912,
-    ],
-    [
-        801.6450002073708,
-        739.9800001503532,
-        647.482499956106,
-        1294.9649999122114,
-        1202.4674997099817,
-        601.2337498549912,
-        701.4393749913753,
-        863.3099998743477,
-    ],
-    [
-        739.9800001503532,
-        1187.0512502627944,
-        678.3149999421656,
-        647.482499956106,
-        593.5256251313969,
-        1017.4724999923628,
-        508.736249996181,
-        863.3099998743477,
-    ],
-    [
-        739.9800001503532,
-        678.3149999421656,
-        616.6500001254908,
-        554.9850001559179,
-        462.4875001300791,
-        693.731249848345,
-        763.1043750536072,
-        508.736249996181,
-    ],
-    [
-        801.6450002073708,
-        739.9800001503532,
-        678.3149999421656,
-        551.1309375264486,
-        1102.2618750528968,
-        601.2337498549912,
-        508.736249996181,
-        1017.4724999923628,
-    ],
-    [
-        739.9800001503532,
-        517.9859999244433,
-        582.7342500056201,
-        647.482499956106,
-        712.230750157449,
-        457.86262503201795,
-        665.9820001868889,
-        508.736249996181,
-    ],
-    [
-        739.9800001503532,
-        647.482499956106,
-        616.6500001254908,
-        462.4875001300791,
-        1079.137499927188,
-        539.5687499635937,
-        924.9750002601583,
-        863.3099998743477,
-    ],
-    [
-        832.4774998177487,
-        739.9800001503532,
-        616.6500001254908,
-        554.9850001559179,
-        462.4875001300791,
-        693.731249848345,
-        1109.970000311835,
-        924.9750002601583,
-    ],
-    [
-        739.9800001503532,
-        485.61187500483805,
-        678.3149999421656,
-        647.482499956106,
-        554.9850001559179,
-        593.5256251313969,
-        763.1043750536072,
-        508.736249996181,
-    ],
-    [
-        450.92531242630474,
-        739.9800001503532,
-        678.3149999421656,
-        551.1309375264486,
-        554.9850001559179,
-        601.2337498549912,
-        763.1043750536072,
-        508.736249996181,
-    ],
-    [
-        832.4774998177487,
-        739.9800001503532,
-        485.61187500483805,
-        647.482499956106,
-        554.9850001559179,
-        971.2237500096761,
-        1109.970000311835,
-        863.3099998743477,
-    ],
-    [
-        739.9800001503532,
-        485.61187500483805,
-        647.482499956106,
-        616.6500001254908,
-        554.9850001559179,
-        462.4875001300791,
-        693.731249848345,
-        539.5687499635937,
-    ],
-    [
-        739.9800001503532,
-        678.3149999421656,
-        551.1309375264486,
-        616.6500001254908,
-        462.4875001300791,
-        501.02812487931885,
-        601.2337498549912,
-        508.736249996181,
-    ],
-    [
-        549.4136869822032,
-        457.84473915198214,
-        523.2511306011972,
-        575.5762434951098,
-        470.9260175775441,
-        503.629213212657,
-        539.6027283607516,
-        479.64686957941154,
-    ],
+pub const ROOT: i32 = 60;
+pub const PROGRESSIONS: [Progression; 7] = [
+    Progression {
+        name: "Functional",
+        chords: &[0, 3, 4, 0],
+    },
+    Progression {
+        name: "Axis",
+        chords: &[0, 4, 5, 3],
+    },
+    Progression {
+        name: "Turnaround",
+        chords: &[0, 5, 1, 4],
+    },
+    Progression {
+        name: "Cadence",
+        chords: &[1, 4, 0, 0],
+    },
+    Progression {
+        name: "Plagal",
+        chords: &[0, 3],
+    },
+    Progression {
+        name: "Vamp",
+        chords: &[0, 6],
+    },
+    Progression {
+        name: "Drone",
+        chords: &[0],
+    },
+];
+pub const CHANGES: [usize; 3] = [1, 2, 4];
+pub const CHORD: f64 = 0.7;
+pub const JUST: [f32; 12] = [
+    1.0,
+    16.0 / 15.0,
+    9.0 / 8.0,
+    6.0 / 5.0,
+    5.0 / 4.0,
+    4.0 / 3.0,
+    45.0 / 32.0,
+    3.0 / 2.0,
+    8.0 / 5.0,
+    5.0 / 3.0,
+    9.0 / 5.0,
+    15.0 / 8.0,
+];
+pub const SCALA: usize = 128;
+pub const MODES: [Mode; 11] = [
+    Mode {
+        name: "Ionian",
+        intervals: &[0, 2, 4, 5, 7, 9, 11],
+    },
+    Mode {
+        name: "Dorian",
+        intervals: &[0, 2, 3, 5, 7, 9, 10],
+    },
+    Mode {
+        name: "Phrygian",
+        intervals: &[0, 1, 3, 5, 7, 8, 10],
+    },
+    Mode {
+        name: "Lydian",
+        intervals: &[0, 2, 4, 6, 7, 9, 11],
+    },
+    Mode {
+        name: "Mixolydian",
+        intervals: &[0, 2, 4, 5, 7, 9, 10],
+    },
+    Mode {
+        name: "Aeolian",
+        intervals: &[0, 2, 3, 5, 7, 8, 10],
+    },
+    Mode {
+        name: "Locrian",
+        intervals: &[0, 1, 3, 5, 6, 8, 10],
+    },
+    Mode {
+        name: "Harmonic Minor",
+        intervals: &[0, 2, 3, 5, 7, 8, 11],
+    },
+    Mode {
+        name: "Major Pentatonic",
+        intervals: &[0, 2, 4, 7, 9],
+    },
+    Mode {
+        name: "Minor Pentatonic",
+        intervals: &[0, 3, 5, 7, 10],
+    },
+    Mode {
+        name: "Whole Tone",
+        intervals: &[0, 2, 4, 6, 8, 10],
+    },
 ];
+pub const SNAPSHOT: usize = 6633;
@@ -0,0 +1,147 @@
+use core::fmt;
+
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng};
+
+use super::line::Line;
+use crate::constants::{DELAY, DELAY_FEEDBACK, DELAY_GLIDE, DELAY_LINE, DELAY_MIX, PPQ};
+use crate::error::Error;
+use crate::snapshot::DelayState;
+use crate::Frame;
+
+#[derive(Clone)]
+pub struct Delay {
+    ticks: usize,
+    feedback: f32,
+    mix: f32,
+    time: Option<f32>,
+}
+
+impl Delay {
+    pub fn new(ticks: usize, feedback: f32, mix: f32) -> Self {
+        Delay {
+            ticks,
+            feedback,
+            mix,
+            time: None,
+        }
+    }
+
+    pub fn random(rng: &mut SmallRng) -> Result<Self, Error> {
+        let ticks = *DELAY.choose(rng).ok_or(Error::Rng)?;
+        let feedback = rng.gen_range(DELAY_FEEDBACK);
+        let mix = rng.gen_range(DELAY_MIX);
+
+        Ok(Self::new(ticks, feedback, mix))
+    }
+
+    pub fn set(&mut self, ticks: usize, feedback: f32, mix: f32) {
+        self.ticks = ticks;
+        self.feedback = feedback;
+        self.mix = mix;
+    }
+
+    pub fn snapshot(&self) -> DelayState {
+        DelayState {
+            ticks: self.ticks,
+            feedback: self.feedback,
+            mix: self.mix,
+        }
+    }
+
+    pub fn process(&mut self, line: &mut Line<DELAY_LINE>, out: &mut [Frame], tempo: f32) {
+        let target = time(self.ticks, tempo);
+        let mut time = self.time.unwrap_or(target);
+
+        for frame in out.iter_mut() {
+            time += (target - time) * DELAY_GLIDE;
+            let delayed = line.tap(time);
+            line.write((frame[0] + frame[1]) * 0.5 + delayed * self.feedback);
+            for sample in frame.iter_mut() {
+                *sample += (delayed - *sample) * self.mix;
+            }
+        }
+
+        self.time = Some(time);
+    }
+}
+
+fn time(ticks: usize, tempo: f32) -> f32 {
+    (ticks as f32 * tempo / PPQ as f32).clamp(1.0, (DELAY_LINE - 1) as f32)
+}
+
+impl fmt::Display for Delay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}, {:.2}, {:.2})", self.ticks, self.feedback, self.mix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::constants::TEMPOS;
+
+    #[test]
+    fn random() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let delay = Delay::random(&mut rng).unwrap();
+        assert!(DELAY.contains(&delay.ticks));
+        assert!(DELAY_FEEDBACK.contains(&delay.feedback));
+        assert!(DELAY_MIX.contains(&delay.mix));
+    }
+
+    #[test]
+    fn time() {
+        assert_eq!(100.0, super::time(48, 200.0));
+        assert_eq!(11025.0, super::time(48, 22050.0));
+        assert_eq!(24806.25, super::time(72, 33075.0));
+        assert_eq!(1.0, super::time(0, 22050.0));
+        assert_eq!((DELAY_LINE - 1) as f32, super::time(72, 1_000_000.0));
+    }
+
+    #[test]
+    fn capacity() {
+        let longest = *DELAY.iter().max().unwrap();
+        let slowest = 48000.0 * 60.0 / TEMPOS.start();
+        assert_eq!(27000.0, super::time(longest, slowest));
+        let slowest = 96000.0 * 60.0 / TEMPOS.start();
+        assert_eq!((DELAY_LINE - 1) as f32, super::time(longest, slowest));
+    }
+
+    #[test]
+    fn echo() {
+        let mut delay = Delay::new(48, 0.5, 0.5);
+        let mut line = Line::new();
+        let mut out = vec![[0.0; 2]; 1000];
+        out[0] = [1.0; 2];
+        delay.process(&mut line, &mut out, 400.0);
+
+        assert_eq!([0.5; 2], out[0]);
+        assert_eq!([0.5; 2], out[200]);
+        assert_eq!([0.25; 2], out[400]);
+        assert_eq!([0.125; 2], out[600]);
+        assert_eq!(4, out[1..].iter().filter(|frame| frame[0] != 0.0).count());
+    }
+
+    #[test]
+    fn tempo() {
+        let mut chunked = Delay::new(48, 0.5, 0.5);
+        let mut expected = vec![[0.0; 2]; 1000];
+        expected[0] = [1.0; 2];
+        let mut actual = expected.clone();
+
+        let mut whole = Delay::new(48, 0.5, 0.5);
+        let mut line = Line::new();
+        whole.process(&mut line, &mut expected, 400.0);
+        line.clear();
+        for chunk in actual.chunks_mut(61) {
+            chunked.process(&mut line, chunk, 400.0);
+        }
+        assert_eq!(expected, actual);
+
+        let mut out = vec![[0.0; 2]; 100];
+        chunked.process(&mut line, &mut out, 800.0);
+        assert!(chunked.time.unwrap() > 200.0 && chunked.time.unwrap() < 400.0);
+    }
+}
@@ -0,0 +1,89 @@
+use libm::{floorf, roundf};
+
+const SCALE: f32 = 16384.0;
+
+#[derive(Clone)]
+pub struct Line<const N: usize> {
+    buffer: [i16; N],
+    position: usize,
+}
+
+impl<const N: usize> Line<N> {
+    pub const fn new() -> Self {
+        Line {
+            buffer: [0; N],
+            position: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.fill(0);
+        self.position = 0;
+    }
+
+    pub fn write(&mut self, sample: f32) {
+        self.buffer[self.position] = roundf(sample * SCALE) as i16;
+        self.position = (self.position + 1) % N;
+    }
+
+    pub fn read(&self, delay: usize) -> f32 {
+        let delay = delay.clamp(1, N);
+        self.buffer[(self.position + N - delay) % N] as f32 / SCALE
+    }
+
+    pub fn tap(&self, delay: f32) -> f32 {
+        let delay = delay.clamp(1.0, (N - 1) as f32);
+        let whole = floorf(delay);
+        let fraction = delay - whole;
+        let near = self.read(whole as usize);
+        let far = self.read(whole as usize + 1);
+        near + (far - near) * fraction
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read() {
+        let mut line = Line::<4>::new();
+        for sample in [0.25, 0.5, 0.75] {
+            line.write(sample);
+        }
+        assert_eq!(0.75, line.read(1));
+        assert_eq!(0.25, line.read(3));
+        assert_eq!(0.0, line.read(4));
+        assert_eq!(0.0, line.read(100));
+        assert_eq!(0.75, line.read(0));
+
+        line.write(1.0);
+        line.write(-1.0);
+        assert_eq!(0.5, line.read(4));
+
+        line.write(0.1);
+        assert_eq!(1638.0 / SCALE, line.read(1));
+        line.write(4.0);
+        assert_eq!(i16::MAX as f32 / SCALE, line.read(1));
+        line.write(f32::NAN);
+        assert_eq!(0.0, line.read(1));
+
+        line.clear();
+        assert_eq!(0.0, line.read(1));
+        line.write(0.5);
+        assert_eq!(0.5, line.read(1));
+        assert_eq!(0.0, line.read(2));
+    }
+
+    #[test]
+    fn tap() {
+        let mut line = Line::<8>::new();
+        for sample in [0.25, 0.5, 0.75] {
+            line.write(sample);
+        }
+        assert_eq!(0.75, line.tap(1.0));
+        assert_eq!(0.625, line.tap(1.5));
+        assert_eq!(0.3125, line.tap(2.75));
+        assert_eq!(0.75, line.tap(0.0));
+    }
+}
@@ -0,0 +1,193 @@
+mod delay;
+mod line;
+mod reverb;
+
+use core::borrow::BorrowMut;
+use core::fmt;
+
+use rand::rngs::SmallRng;
+
+use crate::constants::{DELAY_LINE, DIFFUSER_LINE, REVERB_LINE};
+use crate::error::Error;
+use crate::snapshot::EffectsState;
+use crate::Frame;
+use delay::Delay;
+use line::Line;
+use reverb::Reverb;
+
+pub struct Lines {
+    delay: Line<DELAY_LINE>,
+    reverb: [Line<REVERB_LINE>; 4],
+    diffusers: [Line<DIFFUSER_LINE>; 2],
+}
+
+impl Lines {
+    pub const fn new() -> Self {
+        Lines {
+            delay: Line::new(),
+            reverb: [Line::new(), Line::new(), Line::new(), Line::new()],
+            diffusers: [Line::new(), Line::new()],
+        }
+    }
+
+    fn clear(&mut self) {
+        self.delay.clear();
+        for line in &mut self.reverb {
+            line.clear();
+        }
+        for line in &mut self.diffusers {
+            line.clear();
+        }
+    }
+}
+
+impl Default for Lines {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Effects<L> {
+    sample_rate: f32,
+    delay: Delay,
+    reverb: Reverb,
+    lines: L,
+}
+
+impl<L: BorrowMut<Lines>> Effects<L> {
+    pub fn random(rng: &mut SmallRng, sample_rate: f32, mut lines: L) -> Result<Self, Error> {
+        let delay = Delay::random(rng)?;
+        let reverb = Reverb::random(rng, sample_rate);
+        lines.borrow_mut().clear();
+
+        Ok(Effects {
+            sample_rate,
+            delay,
+            reverb,
+            lines,
+        })
+    }
+
+    pub fn set_reverb(&mut self, room: f32, damp: f32, mix: f32) -> Result<(), Error> {
+        if !reverb(room, damp, mix) {
+            return Err(Error::Control);
+        }
+
+        self.reverb.set(room, damp, mix);
+        Ok(())
+    }
+
+    pub fn set_delay(&mut self, ticks: usize, feedback: f32, mix: f32) -> Result<(), Error> {
+        if !delay(ticks, feedback, mix) {
+            return Err(Error::Control);
+        }
+
+        self.delay.set(ticks, feedback, mix);
+        Ok(())
+    }
+
+    pub fn snapshot(&self) -> EffectsState {
+        EffectsState {
+            delay: self.delay.snapshot(),
+            reverb: self.reverb.snapshot(),
+        }
+    }
+
+    pub fn restore(&mut self, state: &EffectsState) -> Result<(), Error> {
+        let (d, r) = (state.delay, state.reverb);
+        if !delay(d.ticks, d.feedback, d.mix) || !reverb(r.room, r.damp, r.mix) {
+            return Err(Error::Snapshot);
+        }
+
+        self.delay = Delay::new(d.ticks, d.feedback, d.mix);
+        self.reverb = Reverb::new(self.sample_rate, r.room, r.damp, r.mix);
+        self.lines.borrow_mut().clear();
+        Ok(())
+    }
+
+    pub fn process(&mut self, out: &mut [Frame], tempo: f32) {
+        let lines = self.lines.borrow_mut();
+        self.delay.process(&mut lines.delay, out, tempo);
+        self.reverb
+            .process(&mut lines.reverb, &mut lines.diffusers, out);
+    }
+}
+
+fn reverb(room: f32, damp: f32, mix: f32) -> bool {
+    (0.0..1.0).contains(&room) && (0.0..=1.0).contains(&damp) && (0.0..=1.0).contains(&mix)
+}
+
+fn delay(ticks: usize, feedback: f32, mix: f32) -> bool {
+    ticks > 0 && (0.0..1.0).contains(&feedback) && (0.0..=1.0).contains(&mix)
+}
+
+impl<L> fmt::Display for Effects<L> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[DELAY] {} [REVERB] {}", self.delay, self.reverb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn controls() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut effects = Effects::random(&mut rng, 44100.0, Lines::new()).unwrap();
+
+        effects.set_reverb(0.5, 0.25, 0.75).unwrap();
+        effects.set_delay(96, 0.25, 0.5).unwrap();
+        assert_eq!(
+            "[DELAY] (96, 0.25, 0.50) [REVERB] (0.50, 0.25, 0.75)",
+            format!("{}", effects)
+        );
+
+        assert!(effects.set_reverb(1.0, 0.25, 0.75).is_err());
+        assert!(effects.set_reverb(0.5, 0.25, 1.5).is_err());
+        assert!(effects.set_delay(0, 0.25, 0.5).is_err());
+        assert!(effects.set_delay(96, 1.0, 0.5).is_err());
+        assert!(effects.set_delay(96, 0.25, f32::NAN).is_err());
+    }
+
+    #[test]
+    fn restore() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let effects = Effects::random(&mut rng, 44100.0, Lines::new()).unwrap();
+        let state = effects.snapshot();
+
+        let mut restored = Effects::random(&mut rng, 44100.0, Lines::new()).unwrap();
+        restored.restore(&state).unwrap();
+        assert_eq!(state, restored.snapshot());
+
+        let mut invalid = state;
+        invalid.reverb.room = 1.0;
+        assert!(restored.restore(&invalid).is_err());
+        assert_eq!(state, restored.snapshot());
+    }
+
+    #[test]
+    fn lines() {
+        let mut owned =
+            Effects::random(&mut SmallRng::seed_from_u64(0), 44100.0, Lines::new()).unwrap();
+        let mut lines = Lines::new();
+        lines.delay.write(1.0);
+        let mut borrowed =
+            Effects::random(&mut SmallRng::seed_from_u64(0), 44100.0, &mut lines).unwrap();
+
+        let mut expected = vec![[0.0; 2]; 44100];
+        expected[0] = [1.0; 2];
+        let mut actual = expected.clone();
+        owned.process(&mut expected, 22050.0);
+        borrowed.process(&mut actual, 22050.0);
+        assert_eq!(expected, actual);
+
+        let state = borrowed.snapshot();
+        borrowed.restore(&state).unwrap();
+        let mut out = vec![[0.0; 2]; 44100];
+        borrowed.process(&mut out, 22050.0);
+        assert!(out.iter().all(|frame| *frame == [0.0; 2]));
+    }
+}
@@ -0,0 +1,181 @@
+use core::fmt;
+
+use rand::{rngs::SmallRng, Rng};
+
+use super::line::Line;
+use crate::constants::{
+    DAMP, DIFFUSERS, DIFFUSER_LINE, DIFFUSION, REVERB, REVERB_LINE, REVERB_MIX, ROOM,
+};
+use crate::snapshot::ReverbState;
+use crate::Frame;
+
+#[derive(Clone)]
+pub struct Reverb {
+    room: f32,
+    damp: f32,
+    mix: f32,
+    lengths: [usize; 4],
+    filters: [f32; 4],
+    spreads: [usize; 2],
+}
+
+impl Reverb {
+    pub fn new(sample_rate: f32, room: f32, damp: f32, mix: f32) -> Self {
+        Reverb {
+            room,
+            damp,
+            mix,
+            lengths: REVERB.map(|length| scale(length, sample_rate, REVERB_LINE)),
+            filters: [0.0; 4],
+            spreads: DIFFUSERS.map(|length| scale(length, sample_rate, DIFFUSER_LINE)),
+        }
+    }
+
+    pub fn random(rng: &mut SmallRng, sample_rate: f32) -> Self {
+        let room = rng.gen_range(ROOM);
+        let damp = rng.gen_range(DAMP);
+        let mix = rng.gen_range(REVERB_MIX);
+
+        Self::new(sample_rate, room, damp, mix)
+    }
+
+    pub fn set(&mut self, room: f32, damp: f32, mix: f32) {
+        self.room = room;
+        self.damp = damp;
+        self.mix = mix;
+    }
+
+    pub fn snapshot(&self) -> ReverbState {
+        ReverbState {
+            room: self.room,
+            damp: self.damp,
+            mix: self.mix,
+        }
+    }
+
+    pub fn process(
+        &mut self,
+        lines: &mut [Line<REVERB_LINE>; 4],
+        diffusers: &mut [Line<DIFFUSER_LINE>; 2],
+        out: &mut [Frame],
+    ) {
+        for frame in out.iter_mut() {
+            let mut input = (frame[0] + frame[1]) * 0.5;
+            for (diffuser, &spread) in diffusers.iter_mut().zip(&self.spreads) {
+                let delayed = diffuser.read(spread);
+                let sample = input + DIFFUSION * delayed;
+                diffuser.write(sample);
+                input = delayed - DIFFUSION * sample;
+            }
+
+            let mut taps = [0.0; 4];
+            for (index, tap) in taps.iter_mut().enumerate() {
+                *tap = lines[index].read(self.lengths[index]);
+                self.filters[index] += (*tap - self.filters[index]) * (1.0 - self.damp);
+            }
+
+            let reflection = self.filters.iter().sum::<f32>() * 0.5;
+            for (line, filter) in lines.iter_mut().zip(&self.filters) {
+                line.write(input + self.room * (filter - reflection));
+            }
+
+            let wet = [(taps[0] + taps[2]) * 0.5, (taps[1] + taps[3]) * 0.5];
+            for (sample, wet) in frame.iter_mut().zip(wet) {
+                *sample += (wet - *sample) * self.mix;
+            }
+        }
+    }
+}
+
+fn scale(length: usize, sample_rate: f32, capacity: usize) -> usize {
+    ((length as f32 * sample_rate / 44100.0) as usize).clamp(1, capacity)
+}
+
+impl fmt::Display for Reverb {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({:.2}, {:.2}, {:.2})", self.room, self.damp, self.mix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn process(reverb: &mut Reverb, out: &mut [Frame]) {
+        let mut lines = core::array::from_fn(|_| Line::new());
+        let mut diffusers = core::array::from_fn(|_| Line::new());
+        reverb.process(&mut lines, &mut diffusers, out);
+    }
+
+    fn impulse(reverb: &mut Reverb, length: usize) -> Vec<Frame> {
+        let mut out = vec![[0.0; 2]; length];
+        out[0] = [1.0; 2];
+        process(reverb, &mut out);
+        out
+    }
+
+    fn energy(frames: &[Frame]) -> f32 {
+        frames
+            .iter()
+            .map(|frame| frame[0] * frame[0] + frame[1] * frame[1])
+            .sum()
+    }
+
+    #[test]
+    fn random() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let reverb = Reverb::random(&mut rng, 44100.0);
+        assert!(ROOM.contains(&reverb.room));
+        assert!(DAMP.contains(&reverb.damp));
+        assert!(REVERB_MIX.contains(&reverb.mix));
+        assert_eq!(REVERB, reverb.lengths);
+    }
+
+    #[test]
+    fn scale() {
+        assert_eq!(
+            [1183, 1396, 1574, 1810],
+            Reverb::new(48000.0, 0.8, 0.5, 0.5).lengths
+        );
+        assert_eq!(
+            [REVERB_LINE; 4],
+            Reverb::new(192000.0, 0.8, 0.5, 0.5).lengths
+        );
+        assert_eq!(1, super::scale(1, 1.0, 8));
+    }
+
+    #[test]
+    fn dry() {
+        let mut reverb = Reverb::new(44100.0, 0.8, 0.5, 0.0);
+        let out = impulse(&mut reverb, 10_000);
+        assert_eq!([1.0; 2], out[0]);
+        assert!(out[1..].iter().all(|frame| *frame == [0.0; 2]));
+    }
+
+    #[test]
+    fn decay() {
+        let mut reverb = Reverb::new(44100.0, 0.8, 0.5, 1.0);
+        let out = impulse(&mut reverb, 88_200);
+        assert_eq!([0.0; 2], out[0]);
+        assert!(out[..REVERB[0]].iter().all(|frame| *frame == [0.0; 2]));
+        assert_ne!(out[REVERB[0] + DIFFUSERS[0] + DIFFUSERS[1]], [0.0; 2]);
+        assert_ne!(out[10_000][0], out[10_000][1]);
+
+        let early = energy(&out[..22_050]);
+        let late = energy(&out[66_150..]);
+        assert!(late < early * 0.01);
+        assert!(late > 0.0);
+    }
+
+    #[test]
+    fn stable() {
+        let mut reverb = Reverb::new(44100.0, 0.99, 0.0, 1.0);
+        let mut out = vec![[1.0; 2]; 441_000];
+        process(&mut reverb, &mut out);
+        assert!(out
+            .iter()
+            .all(|frame| frame[0].is_finite() && frame[0].abs() < 100.0));
+    }
+}
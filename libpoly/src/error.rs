@@ -1,5 +1,10 @@
 #[derive(Debug)]
 pub enum Error {
     Array,
+    Polyphony,
     Rng,
+    Scala,
+    Snapshot,
+    Control,
+    Config(&'static str),
 }
@@ -0,0 +1,75 @@
+use core::fmt;
+
+use libm::{log2f, roundf};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Event {
+    pub track: usize,
+    pub start: u64,
+    pub length: usize,
+    pub note: u8,
+    pub velocity: u8,
+}
+
+impl Event {
+    pub fn new(track: usize, start: u64, length: usize, frequency: f32, level: f32) -> Self {
+        Event {
+            track,
+            start,
+            length,
+            note: note(frequency),
+            velocity: roundf(level.clamp(0.0, 1.0) * 126.0) as u8 + 1,
+        }
+    }
+}
+
+fn note(frequency: f32) -> u8 {
+    roundf(69.0 + 12.0 * log2f(frequency / 440.0)).clamp(0.0, 127.0) as u8
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[EVENT {}] {} +{} ({}, {})",
+            self.track, self.start, self.length, self.note, self.velocity
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new() {
+        let event = Event::new(1, 10, 20, 440.0, 0.5);
+        assert_eq!(1, event.track);
+        assert_eq!(10, event.start);
+        assert_eq!(20, event.length);
+        assert_eq!(69, event.note);
+        assert_eq!(64, event.velocity);
+    }
+
+    #[test]
+    fn note() {
+        assert_eq!(60, super::note(261.63));
+        assert_eq!(70, super::note(462.4875));
+        assert_eq!(0, super::note(1.0));
+        assert_eq!(127, super::note(100_000.0));
+    }
+
+    #[test]
+    fn velocity() {
+        assert_eq!(1, Event::new(0, 0, 1, 440.0, 0.0).velocity);
+        assert_eq!(127, Event::new(0, 0, 1, 440.0, 2.0).velocity);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            "[EVENT 2] 100 +50 (69, 127)",
+            format!("{}", Event::new(2, 100, 50, 440.0, 1.0))
+        );
+    }
+}
@@ -1,50 +1,813 @@
 #![cfg_attr(not(test), no_std)]
 
 mod clock;
+mod config;
 mod constants;
+mod effects;
 mod error;
+mod event;
 mod logger;
+mod score;
 mod sequence;
+mod snapshot;
 mod synth;
+mod theory;
+mod track;
+mod transport;
+mod tuning;
 
-use rand::{rngs::SmallRng, SeedableRng};
+use core::borrow::BorrowMut;
 
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use clock::Clock;
+pub use config::PolyConfig;
+use constants::{BLOCK, MODES, TRACKS};
+use effects::Effects;
+pub use effects::Lines;
 pub use error::Error;
+pub use event::Event;
 use logger::debug;
-use sequence::Sequence;
-use synth::Synth;
+use score::Score;
+pub use snapshot::Snapshot;
+pub use synth::{Oscillator, Response, Signal, Steal, Timing};
+use theory::Scale;
+use track::Track;
+use transport::{Follower, STEP, TICKS};
+pub use transport::{Role, Transport};
+pub use tuning::{Scala, Tuning};
+
+pub type Frame = [f32; 2];
 
-pub struct Poly {
+pub struct Poly<L = Lines> {
     rng: SmallRng,
-    sequence: Sequence,
-    synth: Synth,
+    score: Score,
+    tracks: [Track; TRACKS.len()],
+    effects: Effects<L>,
+    frame: u64,
+    role: Role,
+    running: bool,
+    follower: Follower,
+    pulse_clock: Clock,
+    pulse: usize,
+    cue: Option<usize>,
+    stop: bool,
 }
 
 impl Poly {
-    pub fn new(seed: u64, sample_rate: u32) -> Result<Self, Error> {
+    pub const TRACKS: usize = TRACKS.len();
+
+    pub fn new(seed: u64, sample_rate: u32, config: &PolyConfig) -> Result<Self, Error> {
+        Self::with_lines(seed, sample_rate, config, Lines::new())
+    }
+}
+
+impl<L: BorrowMut<Lines>> Poly<L> {
+    pub fn with_lines(
+        seed: u64,
+        sample_rate: u32,
+        config: &PolyConfig,
+        lines: L,
+    ) -> Result<Self, Error> {
+        config.validate(sample_rate)?;
+
         let mut rng = SmallRng::seed_from_u64(seed);
         debug!("[SEED] {}", seed);
 
-        let sequence = Sequence::new(&mut rng, sample_rate as f32)?;
-        debug!("{}", sequence);
+        let sample_rate = sample_rate as f32;
+
+        let score = Score::new(&mut rng, sample_rate, config)?;
+        debug!("{}", score);
+
+        let tracks = [
+            Track::new(&mut rng, &score, sample_rate, &TRACKS[0], config)?,
+            Track::new(&mut rng, &score, sample_rate, &TRACKS[1], config)?,
+            Track::new(&mut rng, &score, sample_rate, &TRACKS[2], config)?,
+        ];
+        for (index, track) in tracks.iter().enumerate() {
+            debug!("[TRACK {}] {}", index, track);
+        }
+
+        let seed = rng.gen();
+        let effects = Effects::random(&mut rng, sample_rate, lines)?;
+        debug!("{}", effects);
 
-        let synth = Synth::new(&mut rng, sample_rate as f32)?;
-        debug!("{}", synth);
+        let rng = SmallRng::seed_from_u64(seed);
 
         Ok(Poly {
             rng,
-            sequence,
-            synth,
+            score,
+            tracks,
+            effects,
+            frame: 0,
+            role: Role::Internal,
+            running: true,
+            follower: Follower::new(sample_rate),
+            pulse_clock: Clock::default(),
+            pulse: 0,
+            cue: None,
+            stop: false,
         })
     }
 
-    pub fn next(&mut self) -> f32 {
-        if let Some((length, frequency)) = self.sequence.next(&mut self.rng) {
-            self.synth.play(&mut self.rng, length, frequency);
+    pub fn set_polyphony(&mut self, polyphony: usize, steal: Steal) -> Result<(), Error> {
+        for track in &mut self.tracks {
+            track.set_polyphony(polyphony, steal)?;
+        }
+        Ok(())
+    }
+
+    pub fn set_legato(&mut self, legato: bool) {
+        debug!("[LEGATO] {}", legato);
+        for track in &mut self.tracks {
+            track.set_legato(legato);
+        }
+    }
+
+    pub fn set_tuning(&mut self, tuning: Tuning) {
+        debug!("[TUNING] {}", tuning);
+        self.score.tuning = tuning;
+    }
+
+    pub fn train(&mut self, degrees: &[usize]) {
+        for track in &mut self.tracks {
+            track.train(degrees);
+        }
+    }
+
+    pub fn set_tempo(&mut self, bpm: f32) -> Result<(), Error> {
+        if !(bpm > 0.0 && bpm.is_finite()) {
+            return Err(Error::Control);
+        }
+
+        debug!("[TEMPO] {:.2}bpm", bpm);
+        self.score.frame = self.frame;
+        self.score.set_tempo(bpm);
+        Ok(())
+    }
+
+    pub fn transpose(&mut self, semitones: i32) {
+        self.score.transpose(semitones);
+        debug!("[SCALE] {}", self.score.scale);
+    }
+
+    pub fn set_scale(&mut self, root: i32, mode: usize) -> Result<(), Error> {
+        let mode = MODES.get(mode).ok_or(Error::Control)?;
+        self.score.set_scale(Scale::new(root.rem_euclid(12), mode));
+        debug!("[SCALE] {}", self.score.scale);
+        Ok(())
+    }
+
+    pub fn set_locked(&mut self, locked: bool) {
+        debug!("[LOCKED] {}", locked);
+        self.score.locked = locked;
+        for track in &mut self.tracks {
+            track.set_locked(locked);
+        }
+    }
+
+    pub fn set_mutation(&mut self, rate: f32) -> Result<(), Error> {
+        if !(rate > 0.0 && rate.is_finite()) {
+            return Err(Error::Control);
+        }
+
+        debug!("[MUTATION] x{:.2}", rate);
+        self.score.set_rate(rate);
+        for track in &mut self.tracks {
+            track.set_rate(rate);
+        }
+        Ok(())
+    }
+
+    pub fn set_filter(&mut self, track: usize, cutoff: f32, q: f32) -> Result<(), Error> {
+        self.track(track)?.set_filter(cutoff, q)
+    }
+
+    pub fn set_response(&mut self, track: usize, response: Response) -> Result<(), Error> {
+        self.track(track)?.set_response(response);
+        Ok(())
+    }
+
+    pub fn set_envelope(
+        &mut self,
+        track: usize,
+        operator: usize,
+        attack: f32,
+        decay: f32,
+        sustain: f32,
+        release: f32,
+    ) -> Result<(), Error> {
+        self.track(track)?
+            .set_envelope(operator, attack, decay, sustain, release)
+    }
+
+    pub fn set_curve(
+        &mut self,
+        track: usize,
+        operator: usize,
+        curve: f32,
+        timing: Timing,
+    ) -> Result<(), Error> {
+        self.track(track)?.set_curve(operator, curve, timing)
+    }
+
+    pub fn set_operator(
+        &mut self,
+        track: usize,
+        operator: usize,
+        signal: Signal,
+        oscillator: Oscillator,
+        ratio: f32,
+    ) -> Result<(), Error> {
+        self.track(track)?
+            .set_operator(operator, signal, oscillator, ratio)
+    }
+
+    pub fn set_reverb(&mut self, room: f32, damp: f32, mix: f32) -> Result<(), Error> {
+        self.effects.set_reverb(room, damp, mix)
+    }
+
+    pub fn set_delay(&mut self, ticks: usize, feedback: f32, mix: f32) -> Result<(), Error> {
+        self.effects.set_delay(ticks, feedback, mix)
+    }
+
+    fn track(&mut self, track: usize) -> Result<&mut Track, Error> {
+        self.tracks.get_mut(track).ok_or(Error::Control)
+    }
+
+    pub fn snapshot(&mut self) -> Snapshot {
+        let seed = self.rng.gen();
+        self.rng = SmallRng::seed_from_u64(seed);
+
+        let snapshot = Snapshot {
+            seed,
+            frame: self.frame,
+            score: self.score.snapshot(),
+            tracks: self.tracks.each_mut().map(|track| track.snapshot()),
+            effects: self.effects.snapshot(),
+        };
+        debug!("[SNAPSHOT] {}", snapshot);
+        snapshot
+    }
+
+    /// Restores the generative state and controls from `snapshot`. The tuning is not part of the
+    /// snapshot: like `PolyConfig`, it is set by the host and kept as is.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), Error> {
+        self.score.restore(&snapshot.score)?;
+        for (track, state) in self.tracks.iter_mut().zip(&snapshot.tracks) {
+            track.restore(state)?;
+        }
+        self.effects.restore(&snapshot.effects)?;
+
+        self.rng = SmallRng::seed_from_u64(snapshot.seed);
+        self.frame = snapshot.frame;
+        self.score.frame = snapshot.frame;
+
+        if self.role == Role::Lead {
+            self.cue();
+        }
+
+        debug!("[RESTORE] {}", snapshot);
+        debug!("{}", self.score);
+        for (index, track) in self.tracks.iter().enumerate() {
+            debug!("[TRACK {}] {}", index, track);
+        }
+        debug!("{}", self.effects);
+        Ok(())
+    }
+
+    pub fn set_role(&mut self, role: Role) {
+        debug!("[ROLE] {} -> {}", self.role, role);
+        if !self.running {
+            self.locate(self.follower.tick);
+            self.running = true;
+        }
+        if self.role == Role::Lead && role != Role::Lead {
+            self.stop = true;
+        }
+
+        self.role = role;
+        self.score.following = role == Role::Follow;
+        match role {
+            Role::Follow => {
+                self.running = false;
+                self.follower.tick = 0;
+                self.follower.armed = false;
+            }
+            Role::Lead => self.cue(),
+            Role::Internal => {}
+        }
+    }
+
+    pub fn receive(&mut self, transport: Transport) {
+        if self.role != Role::Follow {
+            return;
+        }
+        if transport != Transport::Clock {
+            debug!("[TRANSPORT] {}", transport);
+        }
+
+        self.score.frame = self.frame;
+        match transport {
+            Transport::Clock => {
+                let bpm = self.follower.clock(self.frame);
+                if self.follower.armed {
+                    if let Some(bpm) = bpm {
+                        self.score.set_tempo(bpm);
+                    }
+                    self.locate(self.follower.tick);
+                    self.follower.armed = false;
+                    self.running = true;
+                } else if self.running {
+                    if let Some(bpm) = bpm {
+                        self.score.follow(self.follower.tick, bpm);
+                    }
+                } else {
+                    return;
+                }
+                self.follower.tick += TICKS;
+            }
+            Transport::Start => {
+                self.follower.tick = 0;
+                self.follower.armed = true;
+            }
+            Transport::Continue => self.follower.armed = true,
+            Transport::Stop => {
+                self.running = false;
+                self.follower.armed = false;
+            }
+            Transport::Position(position) => self.follower.tick = position * STEP,
+        }
+    }
+
+    pub fn bpm(&self) -> f32 {
+        self.score.bpm
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn render(&mut self, out: &mut [Frame]) {
+        self.render_events(out, |_| {});
+    }
+
+    pub fn render_events<F>(&mut self, out: &mut [Frame], emit: F)
+    where
+        F: FnMut(Event),
+    {
+        self.render_transport(out, emit, |_, _| {});
+    }
+
+    pub fn render_transport<F, G>(&mut self, out: &mut [Frame], mut emit: F, mut send: G)
+    where
+        F: FnMut(Event),
+        G: FnMut(u64, Transport),
+    {
+        out.fill([0.0; 2]);
+
+        if self.stop {
+            self.stop = false;
+            send(self.frame, Transport::Stop);
+        }
+
+        if !self.running {
+            for track in &mut self.tracks {
+                track.render(out, self.score.tempo);
+            }
+            self.effects.process(out, self.score.tempo);
+            self.frame += out.len() as u64;
+            return;
+        }
+
+        let mut start = 0;
+        while start < out.len() {
+            self.score.frame = self.frame + start as u64;
+            self.score.next(&mut self.rng);
+
+            for (index, track) in self.tracks.iter_mut().enumerate() {
+                if let Some((length, frequency, velocity)) = track.next(&self.score) {
+                    let start = self.frame + start as u64;
+                    let level = track.level() * velocity;
+                    emit(Event::new(index, start, length, frequency, level));
+                }
+            }
+
+            let lead = self.role == Role::Lead;
+            if lead && self.pulse_clock.tick() {
+                let frame = self.frame + start as u64;
+                if let Some(position) = self.cue.take() {
+                    send(frame, Transport::Position(position));
+                    send(frame, Transport::Continue);
+                }
+                send(frame, Transport::Clock);
+                self.pulse += TICKS;
+                self.pulse_clock.reset(self.score.pulse(self.pulse));
+            }
+
+            let mut remaining = self
+                .tracks
+                .iter()
+                .map(Track::remaining)
+                .fold(self.score.remaining(), usize::min);
+            if lead {
+                remaining = remaining.min(self.pulse_clock.remaining());
+            }
+            let end = out.len().min(start + remaining);
+            self.score.skip(end - start - 1);
+            if lead {
+                self.pulse_clock.advance(end - start - 1);
+            }
+            for track in &mut self.tracks {
+                track.skip(end - start - 1);
+                track.render(&mut out[start..end], self.score.tempo);
+            }
+            self.effects.process(&mut out[start..end], self.score.tempo);
+            start = end;
+        }
+
+        self.frame += out.len() as u64;
+    }
+
+    fn locate(&mut self, tick: usize) {
+        self.score.frame = self.frame;
+        self.score.locate(tick);
+        for track in &mut self.tracks {
+            track.locate(&self.score, tick);
+        }
+    }
+
+    fn cue(&mut self) {
+        self.score.frame = self.frame;
+        let tick = (self.score.time() as usize / STEP + 1) * STEP;
+        self.pulse = tick;
+        self.cue = Some(tick / STEP);
+        self.pulse_clock.reset(self.score.pulse(tick) + 1);
+    }
+
+    pub fn render_interleaved(&mut self, out: &mut [f32], channels: usize) {
+        if channels == 0 {
+            return;
+        }
+
+        let mut frames = [[0.0; 2]; BLOCK];
+        for chunk in out.chunks_mut(BLOCK * channels) {
+            let frames = &mut frames[..chunk.len() / channels];
+            self.render(frames);
+
+            for (frame, samples) in frames.iter().zip(chunk.chunks_exact_mut(channels)) {
+                if channels == 1 {
+                    samples[0] = (frame[0] + frame[1]) * 0.5;
+                } else {
+                    for (channel, sample) in samples.iter_mut().enumerate() {
+                        *sample = frame[channel % 2];
+                    }
+                }
+            }
         }
-        self.synth.next()
     }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use crate::constants::{PPQ, PULSES};
+
+    #[test]
+    fn render() {
+        let mut block = Poly::new(0, 44100, &PolyConfig::default()).unwrap();
+        let mut expected = vec![[0.0; 2]; 100_000];
+        block.render(&mut expected);
+
+        let mut chunked = Poly::new(0, 44100, &PolyConfig::default()).unwrap();
+        let mut actual = vec![[0.0; 2]; 100_000];
+        for chunk in actual.chunks_mut(61) {
+            chunked.render(chunk);
+        }
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn with_lines() {
+        let mut owned = Poly::new(0, 44100, &PolyConfig::default()).unwrap();
+        let mut expected = vec![[0.0; 2]; 100_000];
+        owned.render(&mut expected);
+
+        let mut lines = Lines::new();
+        let mut borrowed = Poly::with_lines(0, 44100, &PolyConfig::default(), &mut lines).unwrap();
+        let mut actual = vec![[0.0; 2]; 100_000];
+        borrowed.render(&mut actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn render_events() {
+        let mut block = Poly::new(0, 44100, &PolyConfig::default()).unwrap();
+        let mut frames = vec![[0.0; 2]; 100_000];
+        let mut expected = Vec::new();
+        block.render_events(&mut frames, |event| expected.push(event));
+        assert!(!expected.is_empty());
+        assert!(expected.iter().all(|event| event.start < 100_000));
+        assert!(expected.windows(2).all(|w| w[0].start <= w[1].start));
+
+        let mut chunked = Poly::new(0, 44100, &PolyConfig::default()).unwrap();
+        let mut actual = Vec::new();
+        for chunk in frames.chunks_mut(61) {
+            chunked.render_events(chunk, |event| actual.push(event));
+        }
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn restore() {
+        let mut original = Poly::new(0, 44100, &PolyConfig::default()).unwrap();
+        let mut frames = vec![[0.0; 2]; 1_000_000];
+        original.render(&mut frames);
+
+        let snapshot = original.snapshot();
+        let mut bytes = [0; Snapshot::SIZE];
+        snapshot.encode(&mut bytes).unwrap();
+
+        let mut restored = Poly::new(1, 44100, &PolyConfig::default()).unwrap();
+        restored
+            .restore(&Snapshot::decode(&bytes).unwrap())
+            .unwrap();
+        assert_eq!(original.bpm(), restored.bpm());
+
+        let mut expected = Vec::new();
+        original.render_events(&mut frames, |event| expected.push(event));
+        let mut actual = Vec::new();
+        restored.render_events(&mut frames, |event| actual.push(event));
+        assert!(!expected.is_empty());
+        assert!(expected[0].start >= 1_000_000);
+        assert_eq!(expected, actual);
+
+        let mut other = Poly::new(0, 48000, &PolyConfig::default()).unwrap();
+        assert!(other.restore(&snapshot).is_err());
+    }
+
+    #[test]
+    fn restore_controls() {
+        let mut original = Poly::new(0, 44100, &PolyConfig::default()).unwrap();
+        original.set_locked(true);
+        original.set_legato(true);
+        original.set_mutation(2.0).unwrap();
+        original.train(&[0, 2, 4]);
+        let snapshot = original.snapshot();
+
+        let mut restored = Poly::new(1, 44100, &PolyConfig::default()).unwrap();
+        restored.set_tuning(Tuning::Just);
+        restored.restore(&snapshot).unwrap();
+        assert_eq!("Just", format!("{}", restored.score.tuning));
+        let state = restored.snapshot();
+        assert!(state.score.locked);
+        assert_eq!(snapshot.score, state.score);
+        let expected = original.snapshot();
+        for (expected, actual) in expected.tracks.iter().zip(&state.tracks) {
+            assert!(actual.sequence.locked && actual.synth.locked && actual.synth.legato);
+            assert_eq!(2.0, actual.synth.rate);
+            assert_eq!(expected.sequence, actual.sequence);
+            assert_eq!(expected.synth, actual.synth);
+        }
+    }
+
+    #[test]
+    fn controls() {
+        let mut poly = Poly::new(0, 44100, &PolyConfig::default()).unwrap();
+        let mut frames = vec![[0.0; 2]; 1000];
+        poly.render(&mut frames);
+
+        poly.set_tempo(140.0).unwrap();
+        assert_eq!(140.0, poly.bpm());
+        assert!(poly.set_tempo(0.0).is_err());
+        assert!(poly.set_tempo(f32::NAN).is_err());
+
+        poly.set_scale(14, 1).unwrap();
+        assert_eq!("D Dorian", format!("{}", poly.score.scale));
+        poly.transpose(-3);
+        assert_eq!("B Dorian", format!("{}", poly.score.scale));
+        assert!(poly.set_scale(0, MODES.len()).is_err());
+
+        poly.set_filter(2, 1500.0, 0.3).unwrap();
+        poly.set_envelope(1, 0, 0.1, 0.1, 0.5, 0.2).unwrap();
+        poly.set_operator(0, 2, Signal::Saw, Oscillator::Naive, 2.0)
+            .unwrap();
+        poly.set_response(1, Response::Bandpass).unwrap();
+        poly.set_curve(2, 1, 3.0, Timing::Absolute).unwrap();
+        poly.set_legato(true);
+        let snapshot = poly.snapshot();
+        assert_eq!(1500.0, snapshot.tracks[2].synth.voice.filter.cutoff);
+        assert_eq!(
+            Response::Bandpass as u8,
+            snapshot.tracks[1].synth.voice.filter.response
+        );
+        assert_eq!(0.5, snapshot.tracks[1].synth.voice.envelopes[0].sustain);
+        assert_eq!(2.0, snapshot.tracks[0].synth.voice.operators[2].ratio);
+        assert_eq!(
+            Oscillator::Naive as u8,
+            snapshot.tracks[0].synth.voice.operators[2].oscillator
+        );
+        assert_eq!(3.0, snapshot.tracks[2].synth.voice.envelopes[1].curve);
+
+        poly.set_reverb(0.8, 0.4, 0.2).unwrap();
+        poly.set_delay(48, 0.3, 0.15).unwrap();
+        let snapshot = poly.snapshot();
+        assert_eq!(0.8, snapshot.effects.reverb.room);
+        assert_eq!(48, snapshot.effects.delay.ticks);
+
+        assert!(poly.set_filter(3, 1500.0, 0.3).is_err());
+        assert!(poly.set_reverb(1.0, 0.4, 0.2).is_err());
+        assert!(poly.set_delay(0, 0.3, 0.15).is_err());
+        assert!(poly.set_envelope(3, 0, 0.1, 0.1, 0.5, 0.2).is_err());
+        assert!(poly
+            .set_operator(3, 0, Signal::Saw, Oscillator::Naive, 2.0)
+            .is_err());
+        assert!(poly.set_response(3, Response::Notch).is_err());
+        assert!(poly.set_curve(3, 1, 3.0, Timing::Absolute).is_err());
+        poly.render(&mut frames);
+    }
+
+    #[test]
+    fn locked() {
+        let notes = |poly: &mut Poly| poly.snapshot().tracks.map(|track| track.sequence.notes);
+        let mut frames = vec![[0.0; 2]; 1_000_000];
+
+        let mut unlocked = Poly::new(0, 44100, &PolyConfig::default()).unwrap();
+        let expected = notes(&mut unlocked);
+        unlocked.render(&mut frames);
+        assert_ne!(expected, notes(&mut unlocked));
+
+        let mut locked = Poly::new(0, 44100, &PolyConfig::default()).unwrap();
+        locked.set_locked(true);
+        locked.render(&mut frames);
+        assert_eq!(expected, notes(&mut locked));
+    }
+
+    #[test]
+    fn set_mutation() {
+        let mut poly = Poly::new(0, 44100, &PolyConfig::default()).unwrap();
+        assert!(poly.set_mutation(0.0).is_err());
+        assert!(poly.set_mutation(f32::INFINITY).is_err());
+
+        let notes = |poly: &mut Poly| poly.snapshot().tracks.map(|track| track.sequence.notes);
+        let expected = notes(&mut poly);
+        poly.set_mutation(100.0).unwrap();
+        let mut frames = vec![[0.0; 2]; 10_000];
+        poly.render(&mut frames);
+        assert_ne!(expected, notes(&mut poly));
+    }
+
+    #[test]
+    fn follow() {
+        let mut poly = Poly::new(0, 48000, &PolyConfig::default()).unwrap();
+        poly.set_role(Role::Follow);
+
+        let mut frames = vec![[0.0; 2]; 1000];
+        let mut events = Vec::new();
+        let mut pulse = |poly: &mut Poly, events: &mut Vec<Event>, transport: Option<Transport>| {
+            if let Some(transport) = transport {
+                poly.receive(transport);
+            }
+            poly.receive(Transport::Clock);
+            poly.render_events(&mut frames, |event| events.push(event));
+        };
+
+        for _ in 0..48 {
+            pulse(&mut poly, &mut events, None);
+        }
+        assert!(events.is_empty());
+
+        let start = poly.frame();
+        pulse(&mut poly, &mut events, Some(Transport::Start));
+        for _ in 0..95 {
+            pulse(&mut poly, &mut events, None);
+        }
+        assert_eq!(120.0, poly.bpm());
+        assert_eq!(start, events[0].start);
+
+        pulse(&mut poly, &mut events, Some(Transport::Stop));
+        let stopped = events.len();
+        for _ in 0..24 {
+            pulse(&mut poly, &mut events, None);
+        }
+        assert_eq!(stopped, events.len());
+
+        poly.receive(Transport::Position(16));
+        let resumed = poly.frame();
+        pulse(&mut poly, &mut events, Some(Transport::Continue));
+        for _ in 0..95 {
+            pulse(&mut poly, &mut events, None);
+        }
+        assert!(events[stopped..].iter().all(|event| event.start >= resumed));
+        assert!(events.len() > stopped);
+    }
+
+    #[test]
+    fn lead() {
+        let mut poly = Poly::new(0, 44100, &PolyConfig::default()).unwrap();
+        let mut frames = vec![[0.0; 2]; 10_000];
+        poly.render(&mut frames);
+        poly.set_role(Role::Lead);
+
+        let mut expected = Vec::new();
+        let mut block = vec![[0.0; 2]; 88_200];
+        poly.render_transport(
+            &mut block,
+            |_| {},
+            |frame, transport| expected.push((frame, transport)),
+        );
+        assert!(matches!(expected[0].1, Transport::Position(_)));
+        assert_eq!(Transport::Continue, expected[1].1);
+        assert_eq!(Transport::Clock, expected[2].1);
+        assert!(expected[0].0 >= 10_000);
+
+        let clocks = expected
+            .iter()
+            .filter(|(_, transport)| *transport == Transport::Clock)
+            .map(|(frame, _)| *frame as f32)
+            .collect::<Vec<f32>>();
+        let interval = (clocks[clocks.len() - 1] - clocks[0]) / (clocks.len() - 1) as f32;
+        let expected_interval = 60.0 * 44100.0 / (poly.bpm() * PULSES as f32);
+        assert!((interval - expected_interval).abs() < 1.0);
+
+        poly.set_role(Role::Internal);
+        let mut actual = Vec::new();
+        poly.render_transport(
+            &mut frames,
+            |_| {},
+            |frame, transport| actual.push((frame, transport)),
+        );
+        assert_eq!(vec![(98_200, Transport::Stop)], actual);
+
+        let mut chunked = Poly::new(0, 44100, &PolyConfig::default()).unwrap();
+        chunked.render(&mut vec![[0.0; 2]; 10_000]);
+        chunked.set_role(Role::Lead);
+        let mut actual = Vec::new();
+        for chunk in block.chunks_mut(61) {
+            chunked.render_transport(
+                chunk,
+                |_| {},
+                |frame, transport| actual.push((frame, transport)),
+            );
+        }
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn config() {
+        let config = PolyConfig::default().tempos(64.0..=64.0).metres(&[3]);
+        let mut poly = Poly::new(0, 44100, &config).unwrap();
+        assert_eq!(64.0, poly.bpm());
+        let snapshot = poly.snapshot();
+        assert_eq!(3, snapshot.score.beats);
+        assert!(snapshot
+            .tracks
+            .iter()
+            .all(|track| track.sequence.length == 3 && track.sequence.bar == PPQ * 3));
+
+        let mut frames = vec![[0.0; 2]; 1_000_000];
+        poly.render(&mut frames);
+        assert_eq!(64.0, poly.bpm());
+
+        let config = PolyConfig::default().filter(400.0..=30_000.0, 0.2..=0.5);
+        assert!(Poly::new(0, 44100, &config).is_err());
+        assert!(Poly::new(0, 96000, &config.tempos(140.0..=160.0)).is_ok());
+        assert!(Poly::new(0, 96000, &PolyConfig::default()).is_err());
+    }
+
+    #[test]
+    fn render_interleaved() {
+        let mut frames = Poly::new(0, 44100, &PolyConfig::default()).unwrap();
+        let mut expected = vec![[0.0; 2]; 1024];
+        frames.render(&mut expected);
+
+        let mut stereo = Poly::new(0, 44100, &PolyConfig::default()).unwrap();
+        let mut actual = vec![0.0; 2048];
+        stereo.render_interleaved(&mut actual, 2);
+        assert_eq!(expected.concat(), actual);
+
+        let mut quad = Poly::new(0, 44100, &PolyConfig::default()).unwrap();
+        let mut actual = vec![0.0; 4096];
+        quad.render_interleaved(&mut actual, 4);
+        assert_eq!(
+            expected
+                .iter()
+                .flat_map(|frame| [frame[0], frame[1], frame[0], frame[1]])
+                .collect::<Vec<f32>>(),
+            actual
+        );
+
+        let mut mono = Poly::new(0, 44100, &PolyConfig::default()).unwrap();
+        let mut actual = vec![0.0; 1024];
+        mono.render_interleaved(&mut actual, 1);
+        assert_eq!(
+            expected
+                .iter()
+                .map(|frame| (frame[0] + frame[1]) * 0.5)
+                .collect::<Vec<f32>>(),
+            actual
+        );
+    }
+}
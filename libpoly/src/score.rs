@@ -0,0 +1,745 @@
+use core::fmt;
+
+use core::ops::RangeInclusive;
+
+use libm::round;
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng};
+
+use crate::clock::{interval, Clock};
+use crate::config::PolyConfig;
+use crate::constants::{
+    CHANGES, DRIFT, FOLLOW, METRE, METRES, MODES, MODULATE, PPQ, PROGRESSIONS, SWING, TEMPOS,
+    TEMPO_RAMP, TEMPO_STEP,
+};
+use crate::error::Error;
+use crate::logger::debug;
+use crate::snapshot::ScoreState;
+use crate::theory::{Progression, Scale};
+use crate::tuning::Tuning;
+
+pub struct Score {
+    pub bpm: f32,
+    pub tempo: f32,
+    pub swing: f32,
+    pub beats: usize,
+    pub scale: Scale,
+    pub progression: &'static Progression,
+    pub changes: usize,
+    pub tuning: Tuning,
+    pub frame: u64,
+    pub locked: bool,
+    pub following: bool,
+    rate: f32,
+    tempos: RangeInclusive<f32>,
+    metres: &'static [usize],
+    sample_rate: f32,
+    target: f32,
+    origin_tick: f64,
+    origin_frame: f64,
+    cycle: usize,
+    pivot: Option<usize>,
+    key: Option<Scale>,
+    metre: Option<usize>,
+    drift_clock: Clock,
+    ramp_clock: Clock,
+    modulate_clock: Clock,
+    metre_clock: Clock,
+}
+
+impl Score {
+    pub fn new(rng: &mut SmallRng, sample_rate: f32, config: &PolyConfig) -> Result<Self, Error> {
+        let bpm = rng.gen_range(config.tempos.clone());
+        let tempo = (60.0 / bpm) * sample_rate;
+        let scale = Scale::random(rng)?;
+        let swing = rng.gen_range(SWING);
+        let beats = *config.metres.choose(rng).ok_or(Error::Rng)?;
+        let progression = PROGRESSIONS.choose(rng).ok_or(Error::Rng)?;
+        let changes = *CHANGES.choose(rng).ok_or(Error::Rng)?;
+
+        let drift_clock = Clock::deadline(rng.gen_range(DRIFT));
+        let ramp_clock = Clock::deadline(TEMPO_STEP);
+        let modulate_clock = Clock::deadline(rng.gen_range(MODULATE));
+        let metre_clock = Clock::deadline(rng.gen_range(METRE));
+
+        Ok(Score {
+            bpm,
+            tempo,
+            swing,
+            beats,
+            scale,
+            progression,
+            changes,
+            tuning: Tuning::default(),
+            frame: 0,
+            locked: false,
+            following: false,
+            rate: 1.0,
+            tempos: config.tempos.clone(),
+            metres: config.metres,
+            sample_rate,
+            target: bpm,
+            origin_tick: 0.0,
+            origin_frame: 0.0,
+            cycle: 0,
+            pivot: None,
+            key: None,
+            metre: None,
+            drift_clock,
+            ramp_clock,
+            modulate_clock,
+            metre_clock,
+        })
+    }
+
+    pub fn next(&mut self, rng: &mut SmallRng) {
+        if let Some(pivot) = self.pivot {
+            if self.at(pivot) <= self.frame as f64 {
+                self.pivot(pivot);
+            }
+        }
+
+        if self.drift_clock.tick() {
+            self.drift_clock
+                .reset(interval(rng.gen_range(DRIFT), self.rate));
+            if !self.locked && !self.following {
+                self.target = rng.gen_range(self.tempos.clone());
+                debug!("[TEMPO] {:.2}bpm -> {:.2}bpm", self.bpm, self.target);
+            }
+        }
+
+        if self.ramp_clock.tick() {
+            self.ramp_clock.reset(TEMPO_STEP);
+            self.ramp();
+        }
+
+        if self.modulate_clock.tick() {
+            self.modulate_clock
+                .reset(interval(rng.gen_range(MODULATE), self.rate));
+            if !self.locked {
+                self.key = Some(self.scale.related(rng));
+                self.schedule();
+            }
+        }
+
+        if self.metre_clock.tick() {
+            self.metre_clock
+                .reset(interval(rng.gen_range(METRE), self.rate));
+            if !self.locked {
+                self.metre = self.metres.choose(rng).copied();
+                self.schedule();
+            }
+        }
+    }
+
+    pub fn remaining(&self) -> usize {
+        let remaining = self
+            .drift_clock
+            .remaining()
+            .min(self.ramp_clock.remaining())
+            .min(self.modulate_clock.remaining())
+            .min(self.metre_clock.remaining());
+
+        match self.pivot {
+            Some(pivot) => remaining.min(self.until(pivot)),
+            None => remaining,
+        }
+    }
+
+    pub fn skip(&mut self, samples: usize) {
+        self.drift_clock.advance(samples);
+        self.ramp_clock.advance(samples);
+        self.modulate_clock.advance(samples);
+        self.metre_clock.advance(samples);
+    }
+
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.target = bpm;
+        self.retime(bpm);
+    }
+
+    pub fn follow(&mut self, tick: usize, bpm: f32) {
+        self.target = bpm;
+        self.retime(bpm);
+        self.origin_frame += (self.frame as f64 - self.straight(tick as f64)) * FOLLOW;
+    }
+
+    pub fn locate(&mut self, tick: usize) {
+        self.origin_tick = tick as f64;
+        self.origin_frame = self.frame as f64;
+        self.cycle = 0;
+        if self.pivot.take().is_some() {
+            self.schedule();
+        }
+    }
+
+    pub fn time(&self) -> f64 {
+        self.origin_tick + (self.frame as f64 - self.origin_frame) * PPQ as f64 / self.tempo as f64
+    }
+
+    pub fn pulse(&self, tick: usize) -> usize {
+        (round(self.straight(tick as f64)) - self.frame as f64).max(1.0) as usize
+    }
+
+    pub fn transpose(&mut self, semitones: i32) {
+        self.scale = self.scale.transpose(semitones);
+        self.key = self.key.map(|key| key.transpose(semitones));
+    }
+
+    pub fn set_rate(&mut self, rate: f32) {
+        let factor = self.rate / rate;
+        self.drift_clock.scale(factor);
+        self.modulate_clock.scale(factor);
+        self.metre_clock.scale(factor);
+        self.rate = rate;
+    }
+
+    pub fn set_scale(&mut self, scale: Scale) {
+        self.scale = scale;
+        self.key = None;
+    }
+
+    pub fn snapshot(&self) -> ScoreState {
+        ScoreState {
+            sample_rate: self.sample_rate,
+            bpm: self.bpm,
+            target: self.target,
+            swing: self.swing,
+            beats: self.beats,
+            scale: self.scale.snapshot(),
+            progression: PROGRESSIONS
+                .iter()
+                .position(|progression| progression.name == self.progression.name)
+                .unwrap_or(0),
+            changes: self.changes,
+            origin_tick: self.origin_tick,
+            origin_frame: self.origin_frame,
+            cycle: self.cycle,
+            pivot: self.pivot,
+            key: self.key.map(|key| key.snapshot()),
+            metre: self.metre,
+            locked: self.locked,
+            rate: self.rate,
+            clocks: [
+                self.drift_clock.snapshot(),
+                self.ramp_clock.snapshot(),
+                self.modulate_clock.snapshot(),
+                self.metre_clock.snapshot(),
+            ],
+        }
+    }
+
+    pub fn restore(&mut self, state: &ScoreState) -> Result<(), Error> {
+        let positive = |value: f32| value > 0.0 && value.is_finite();
+        let valid = positive(state.bpm)
+            && positive(state.target)
+            && positive(state.rate)
+            && SWING.contains(&state.swing)
+            && state.origin_tick.is_finite()
+            && state.origin_frame.is_finite();
+        if !valid
+            || state.sample_rate != self.sample_rate
+            || state.beats == 0
+            || state.changes == 0
+            || state.metre == Some(0)
+        {
+            return Err(Error::Snapshot);
+        }
+
+        let scale = Scale::try_from(state.scale)?;
+        let key = state.key.map(Scale::try_from).transpose()?;
+        let progression = PROGRESSIONS.get(state.progression).ok_or(Error::Snapshot)?;
+
+        self.bpm = state.bpm;
+        self.tempo = (60.0 / state.bpm) * self.sample_rate;
+        self.target = state.target;
+        self.swing = state.swing;
+        self.beats = state.beats;
+        self.scale = scale;
+        self.progression = progression;
+        self.changes = state.changes;
+        self.origin_tick = state.origin_tick;
+        self.origin_frame = state.origin_frame;
+        self.cycle = state.cycle;
+        self.pivot = state.pivot;
+        self.key = key;
+        self.metre = state.metre;
+        self.locked = state.locked;
+        self.rate = state.rate;
+        self.drift_clock.restore(&state.clocks[0]);
+        self.ramp_clock.restore(&state.clocks[1]);
+        self.modulate_clock.restore(&state.clocks[2]);
+        self.metre_clock.restore(&state.clocks[3]);
+        Ok(())
+    }
+
+    pub fn frequency(&self, degree: usize, octave: i32) -> Option<f32> {
+        self.tuning.frequency(&self.scale, degree, octave)
+    }
+
+    pub fn chord(&self, position: usize, tone: usize) -> usize {
+        let chord = self
+            .progression
+            .chord(position.saturating_sub(self.cycle) / (self.bar() * self.changes));
+        self.scale.triad(self.scale.chord(chord), tone)
+    }
+
+    pub fn bar(&self) -> usize {
+        self.beats * PPQ
+    }
+
+    pub fn until(&self, position: usize) -> usize {
+        (self.at(position) - self.frame as f64).max(1.0) as usize
+    }
+
+    pub fn cue(&self, position: usize) -> usize {
+        (self.at(position) - self.frame as f64).max(0.0) as usize + 1
+    }
+
+    fn at(&self, position: usize) -> f64 {
+        round(self.straight(self.swung(position)))
+    }
+
+    fn straight(&self, time: f64) -> f64 {
+        self.origin_frame + (time - self.origin_tick) * self.tempo as f64 / PPQ as f64
+    }
+
+    fn swung(&self, position: usize) -> f64 {
+        let half = (PPQ / 2) as f64;
+        let beat = (position / PPQ * PPQ) as f64;
+        let tick = (position % PPQ) as f64;
+        let swing = self.swing as f64;
+
+        if tick < half {
+            beat + tick * (1.0 + swing)
+        } else {
+            beat + half * (1.0 + swing) + (tick - half) * (1.0 - swing)
+        }
+    }
+
+    fn ramp(&mut self) {
+        if self.bpm == self.target {
+            return;
+        }
+
+        let bpm = if self.bpm < self.target {
+            (self.bpm + TEMPO_RAMP).min(self.target)
+        } else {
+            (self.bpm - TEMPO_RAMP).max(self.target)
+        };
+        self.retime(bpm);
+    }
+
+    fn retime(&mut self, bpm: f32) {
+        self.origin_tick = self.time();
+        self.origin_frame = self.frame as f64;
+
+        self.bpm = bpm;
+        self.tempo = (60.0 / self.bpm) * self.sample_rate;
+    }
+
+    fn schedule(&mut self) {
+        if self.pivot.is_some() {
+            return;
+        }
+
+        let cycle = self.bar() * self.changes * self.progression.chords.len();
+        let mut pivot = self.cycle;
+        while self.at(pivot) <= self.frame as f64 {
+            pivot += cycle;
+        }
+        self.pivot = Some(pivot);
+    }
+
+    fn pivot(&mut self, pivot: usize) {
+        if let Some(scale) = self.key.take() {
+            debug!("[SCALE] {} -> {}", self.scale, scale);
+            self.scale = scale;
+        }
+        if let Some(beats) = self.metre.take() {
+            debug!("[METRE] {}/4 -> {}/4", self.beats, beats);
+            self.beats = beats;
+        }
+
+        self.cycle = pivot;
+        self.pivot = None;
+    }
+}
+
+impl Default for Score {
+    fn default() -> Self {
+        Score {
+            bpm: 60.0,
+            tempo: PPQ as f32,
+            swing: 0.0,
+            beats: 4,
+            scale: Scale::new(0, &MODES[0]),
+            progression: &PROGRESSIONS[0],
+            changes: 1,
+            tuning: Tuning::default(),
+            frame: 0,
+            locked: false,
+            following: false,
+            rate: 1.0,
+            tempos: TEMPOS,
+            metres: &METRES,
+            sample_rate: PPQ as f32,
+            target: 60.0,
+            origin_tick: 0.0,
+            origin_frame: 0.0,
+            cycle: 0,
+            pivot: None,
+            key: None,
+            metre: None,
+            drift_clock: Clock::deadline(usize::MAX),
+            ramp_clock: Clock::deadline(usize::MAX),
+            modulate_clock: Clock::deadline(usize::MAX),
+            metre_clock: Clock::deadline(usize::MAX),
+        }
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[TEMPO] {:.2}bpm {}/4 [SWING] {:.2} [SCALE] {} [PROGRESSION] {} x{} [TUNING] {}",
+            self.bpm,
+            self.beats,
+            self.swing,
+            self.scale,
+            self.progression.numerals(&self.scale),
+            self.changes,
+            self.tuning
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn run(score: &mut Score, rng: &mut SmallRng, frames: u64) {
+        while score.frame < frames {
+            score.next(rng);
+            let remaining = score.remaining();
+            score.skip(remaining - 1);
+            score.frame += remaining as u64;
+        }
+    }
+
+    #[test]
+    fn new() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let score = Score::new(&mut rng, 100.0, &PolyConfig::default()).unwrap();
+        assert_eq!(97.893005, score.bpm);
+        assert_eq!(61.29141, score.tempo);
+        assert!(SWING.contains(&score.swing));
+        assert!(METRES.contains(&score.beats));
+        assert!(CHANGES.contains(&score.changes));
+        assert!(score.pivot.is_none());
+
+        let config = PolyConfig::default().metres(&[5]);
+        let mut score = Score::new(&mut rng, 100.0, &config).unwrap();
+        assert_eq!(5 * PPQ, score.bar());
+
+        score.metre_clock = Clock::deadline(10);
+        let cycle = score.bar() * score.changes * score.progression.chords.len();
+        run(&mut score, &mut rng, cycle as u64 * 2);
+        assert_eq!(5 * PPQ, score.bar());
+    }
+
+    #[test]
+    fn until() {
+        let mut score = Score::default();
+        assert_eq!(24, score.until(24));
+        assert_eq!(PPQ * 4, score.until(PPQ * 4));
+        assert_eq!(PPQ * 4, score.bar());
+
+        score.frame = PPQ as u64 * 3;
+        assert_eq!(PPQ, score.until(PPQ * 4));
+        assert_eq!(1, score.until(PPQ));
+
+        score.frame = 0;
+        score.swing = 1.0 / 3.0;
+        assert_eq!(64, score.until(PPQ / 2));
+        assert_eq!(PPQ, score.until(PPQ));
+        assert_eq!(PPQ * 5 + 64, score.until(PPQ * 5 + PPQ / 2));
+
+        score.swing = 0.0;
+        score.tempo = 44100.0 * 60.0 / 97.0;
+        assert_eq!(44100 * 60, score.until(PPQ * 97));
+    }
+
+    #[test]
+    fn ramp() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut score = Score {
+            sample_rate: 44100.0,
+            tempo: 44100.0,
+            target: 60.0 + TEMPO_RAMP * 10.0,
+            ramp_clock: Clock::deadline(TEMPO_STEP),
+            ..Default::default()
+        };
+
+        let before = score.until(PPQ * 1000);
+        run(&mut score, &mut rng, TEMPO_STEP as u64 * 20);
+        assert_eq!(score.target, score.bpm);
+        assert_eq!((60.0 / score.bpm) * 44100.0, score.tempo);
+        assert!(score.until(PPQ * 1000) < before);
+    }
+
+    #[test]
+    fn continuity() {
+        let mut score = Score {
+            sample_rate: 44100.0,
+            tempo: 44100.0,
+            ..Default::default()
+        };
+
+        score.frame = 1000;
+        assert_eq!(44100.0, score.at(PPQ));
+
+        score.target = 120.0;
+        score.ramp();
+        let tempo = (60.0 / (60.0 + TEMPO_RAMP)) * 44100.0;
+        assert_eq!(
+            round(1000.0 + 43100.0 * tempo as f64 / 44100.0),
+            score.at(PPQ)
+        );
+    }
+
+    #[test]
+    fn set_tempo() {
+        let mut score = Score {
+            sample_rate: 44100.0,
+            tempo: 44100.0,
+            ..Default::default()
+        };
+
+        score.frame = 1000;
+        score.set_tempo(120.0);
+        assert_eq!(120.0, score.bpm);
+        assert_eq!(120.0, score.target);
+        assert_eq!(22050.0, score.tempo);
+        assert_eq!(22550.0, score.at(PPQ));
+    }
+
+    #[test]
+    fn transpose() {
+        let mut score = Score {
+            scale: Scale::new(9, &MODES[5]),
+            key: Some(Scale::new(4, &MODES[5])),
+            ..Default::default()
+        };
+
+        score.transpose(5);
+        assert_eq!(2, score.scale.root);
+        assert_eq!(9, score.key.unwrap().root);
+
+        score.transpose(-3);
+        assert_eq!(11, score.scale.root);
+        assert_eq!("Aeolian", score.scale.mode.name);
+
+        score.set_scale(Scale::new(0, &MODES[1]));
+        assert_eq!("C Dorian", format!("{}", score.scale));
+        assert!(score.key.is_none());
+    }
+
+    #[test]
+    fn set_rate() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut score = Score {
+            drift_clock: Clock::deadline(1000),
+            modulate_clock: Clock::deadline(2000),
+            ..Default::default()
+        };
+
+        score.set_rate(4.0);
+        assert_eq!(250, score.drift_clock.remaining());
+        assert_eq!(500, score.modulate_clock.remaining());
+
+        run(&mut score, &mut rng, 250);
+        assert!(score.drift_clock.remaining() <= *DRIFT.end() / 4);
+    }
+
+    #[test]
+    fn locked() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut score = Score {
+            locked: true,
+            drift_clock: Clock::deadline(10),
+            modulate_clock: Clock::deadline(10),
+            metre_clock: Clock::deadline(10),
+            ..Default::default()
+        };
+
+        run(&mut score, &mut rng, 100);
+        assert_eq!(60.0, score.target);
+        assert!(score.key.is_none());
+        assert!(score.metre.is_none());
+        assert!(score.pivot.is_none());
+        assert!(!score.modulate_clock.ready());
+    }
+
+    #[test]
+    fn modulate() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut score = Score {
+            modulate_clock: Clock::deadline(10),
+            ..Default::default()
+        };
+        let cycle = score.bar() * score.progression.chords.len();
+
+        run(&mut score, &mut rng, 100);
+        assert_eq!(Some(cycle), score.pivot);
+        assert_eq!(0, score.scale.root);
+
+        run(&mut score, &mut rng, cycle as u64);
+        assert_eq!(cycle as u64, score.frame);
+        assert_eq!(Some(cycle), score.pivot);
+
+        run(&mut score, &mut rng, cycle as u64 + 1);
+        assert_eq!(None, score.pivot);
+        assert!([5, 7].contains(&score.scale.root));
+        assert_eq!("Ionian", score.scale.mode.name);
+        assert_eq!(cycle, score.cycle);
+        assert_eq!(0, score.chord(cycle, 0));
+    }
+
+    #[test]
+    fn metre() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut score = Score {
+            metre_clock: Clock::deadline(10),
+            ..Default::default()
+        };
+        let cycle = score.bar() * score.progression.chords.len();
+
+        run(&mut score, &mut rng, cycle as u64 + 1);
+        assert_eq!(None, score.pivot);
+        assert_eq!(cycle, score.cycle);
+        assert!(METRES.contains(&score.beats));
+        assert_eq!(3, score.chord(cycle + score.bar(), 0));
+    }
+
+    #[test]
+    fn metres() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut score = Score {
+            metres: &[7],
+            metre_clock: Clock::deadline(10),
+            ..Default::default()
+        };
+        let cycle = score.bar() * score.progression.chords.len();
+
+        run(&mut score, &mut rng, cycle as u64 + 1);
+        assert_eq!(7, score.beats);
+        assert_eq!(7 * PPQ, score.bar());
+    }
+
+    #[test]
+    fn frequency() {
+        let mut score = Score {
+            scale: Scale::new(9, &MODES[5]),
+            ..Default::default()
+        };
+        assert_eq!(Some(440.0), score.frequency(0, 0));
+        assert_eq!(Some(220.0), score.frequency(0, -1));
+        assert_eq!(Some(880.0), score.frequency(7, 0));
+        assert_eq!(Some(523.2511), score.frequency(2, 0));
+
+        score.tuning = Tuning::Just;
+        assert_eq!(Some(528.0), score.frequency(2, 0));
+    }
+
+    #[test]
+    fn chord() {
+        let mut score = Score::default();
+        assert_eq!(0, score.chord(0, 0));
+        assert_eq!(4, score.chord(PPQ * 3, 2));
+        assert_eq!(3, score.chord(PPQ * 4, 0));
+        assert_eq!(6, score.chord(PPQ * 8, 1));
+        assert_eq!(0, score.chord(PPQ * 16, 0));
+
+        score.changes = 2;
+        assert_eq!(0, score.chord(PPQ * 4, 0));
+        assert_eq!(3, score.chord(PPQ * 8, 0));
+
+        score.scale = Scale::new(0, &MODES[9]);
+        assert_eq!(0, score.chord(0, 0));
+        assert_eq!(4, score.chord(PPQ * 8, 1));
+        assert_eq!(3, score.chord(PPQ * 16, 0));
+    }
+
+    #[test]
+    fn restore() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut score = Score::new(&mut rng, 100.0, &PolyConfig::default()).unwrap();
+        run(&mut score, &mut rng, 1_000_000);
+        let state = score.snapshot();
+
+        let mut restored = Score::new(&mut rng, 100.0, &PolyConfig::default()).unwrap();
+        restored.restore(&state).unwrap();
+        restored.frame = score.frame;
+        assert_eq!(state, restored.snapshot());
+        assert_eq!(score.tempo, restored.tempo);
+        assert_eq!(format!("{}", score), format!("{}", restored));
+
+        let mut other = SmallRng::seed_from_u64(1);
+        run(&mut score, &mut rng.clone(), 5_000_000);
+        run(&mut restored, &mut rng, 5_000_000);
+        assert_eq!(score.snapshot(), restored.snapshot());
+
+        let mut invalid = Score::new(&mut other, 44100.0, &PolyConfig::default()).unwrap();
+        assert!(invalid.restore(&state).is_err());
+
+        let mut invalid = state;
+        invalid.bpm = f32::NAN;
+        assert!(restored.restore(&invalid).is_err());
+
+        let mut invalid = state;
+        invalid.bpm = f32::INFINITY;
+        assert!(restored.restore(&invalid).is_err());
+
+        let mut invalid = state;
+        invalid.target = f32::NAN;
+        assert!(restored.restore(&invalid).is_err());
+
+        let mut invalid = state;
+        invalid.target = 0.0;
+        assert!(restored.restore(&invalid).is_err());
+
+        let mut invalid = state;
+        invalid.swing = f32::NAN;
+        assert!(restored.restore(&invalid).is_err());
+
+        let mut invalid = state;
+        invalid.swing = *SWING.end() + 0.1;
+        assert!(restored.restore(&invalid).is_err());
+
+        let mut invalid = state;
+        invalid.origin_tick = f64::NAN;
+        assert!(restored.restore(&invalid).is_err());
+
+        let mut invalid = state;
+        invalid.origin_frame = f64::INFINITY;
+        assert!(restored.restore(&invalid).is_err());
+    }
+
+    #[test]
+    fn display() {
+        let score = Score {
+            bpm: 120.0,
+            swing: 0.25,
+            beats: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            "[TEMPO] 120.00bpm 3/4 [SWING] 0.25 [SCALE] C Ionian [PROGRESSION] Functional (I-IV-V-I) x1 [TUNING] 12-EDO",
+            format!("{}", score)
+        );
+    }
+}
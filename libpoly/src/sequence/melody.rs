@@ -0,0 +1,232 @@
+use core::ops::RangeInclusive;
+
+use rand::{rngs::SmallRng, Rng};
+
+use crate::constants::{DEGREES, GRAVITY, LEAP, MOTION, RECOVERY};
+use crate::snapshot::MelodyState;
+use crate::theory::Scale;
+
+const INTERVALS: usize = MOTION.len() * 2 - 1;
+
+pub struct Melody {
+    first: [[u32; DEGREES]; DEGREES],
+    second: [[[u32; DEGREES]; DEGREES]; DEGREES],
+    order: usize,
+}
+
+impl Melody {
+    pub fn new(order: usize) -> Self {
+        Melody {
+            first: [[1; DEGREES]; DEGREES],
+            second: [[[1; DEGREES]; DEGREES]; DEGREES],
+            order,
+        }
+    }
+
+    pub fn train(&mut self, degrees: &[usize]) {
+        for window in degrees.windows(2) {
+            self.first[window[0] % DEGREES][window[1] % DEGREES] += 1;
+        }
+        for window in degrees.windows(3) {
+            self.second[window[0] % DEGREES][window[1] % DEGREES][window[2] % DEGREES] += 1;
+        }
+    }
+
+    pub fn snapshot(&self) -> MelodyState {
+        MelodyState {
+            first: self.first,
+            second: self.second,
+        }
+    }
+
+    pub fn restore(&mut self, state: &MelodyState) {
+        self.first = state.first;
+        self.second = state.second;
+    }
+
+    pub fn next(
+        &self,
+        rng: &mut SmallRng,
+        scale: &Scale,
+        octaves: &RangeInclusive<i32>,
+        history: [Option<i32>; 2],
+    ) -> i32 {
+        let length = scale.len() as i32;
+        let low = octaves.start() * length;
+        let high = (octaves.end() + 1) * length - 1;
+        let centre = (low + high) / 2;
+
+        let current = history[0].unwrap_or(centre);
+        let leap = match history {
+            [Some(current), Some(previous)] => current - previous,
+            _ => 0,
+        };
+
+        let mut weights = [0.0; INTERVALS];
+        for (index, weight) in weights.iter_mut().enumerate() {
+            let interval = interval(index);
+            let candidate = current + interval;
+            if candidate < low || candidate > high {
+                continue;
+            }
+
+            *weight = MOTION[interval.unsigned_abs() as usize];
+            if leap.abs() >= LEAP && interval.signum() == -leap.signum() && interval.abs() <= 2 {
+                *weight *= RECOVERY;
+            }
+            if interval.signum() == (centre - current).signum() {
+                *weight *= GRAVITY;
+            }
+            *weight *= self.transition(history, length, candidate) as f32;
+        }
+
+        let total = weights.iter().sum::<f32>();
+        if total <= 0.0 {
+            return current.clamp(low, high);
+        }
+
+        let mut target = rng.gen_range(0.0..total);
+        let mut choice = current;
+        for (index, weight) in weights.iter().enumerate() {
+            if *weight > 0.0 {
+                choice = current + interval(index);
+                if target < *weight {
+                    break;
+                }
+            }
+            target -= weight;
+        }
+        choice
+    }
+
+    fn transition(&self, history: [Option<i32>; 2], length: i32, candidate: i32) -> u32 {
+        let degree = |pitch: i32| pitch.rem_euclid(length) as usize % DEGREES;
+
+        match history {
+            [Some(current), Some(previous)] if self.order >= 2 => {
+                self.second[degree(previous)][degree(current)][degree(candidate)]
+            }
+            [Some(current), _] if self.order >= 1 => self.first[degree(current)][degree(candidate)],
+            _ => 1,
+        }
+    }
+}
+
+fn interval(index: usize) -> i32 {
+    index as i32 - (MOTION.len() as i32 - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::constants::MODES;
+
+    fn melody(melody: &Melody, rng: &mut SmallRng, octaves: RangeInclusive<i32>) -> Vec<i32> {
+        let scale = Scale::new(0, &MODES[0]);
+        let mut pitches = Vec::new();
+        let mut history = [None; 2];
+        for _ in 0..2000 {
+            let pitch = melody.next(rng, &scale, &octaves, history);
+            history = [Some(pitch), history[0]];
+            pitches.push(pitch);
+        }
+        pitches
+    }
+
+    #[test]
+    fn intervals() {
+        for seed in 0..8 {
+            let mut rng = SmallRng::seed_from_u64(seed);
+
+            let pitches = melody(&Melody::new(2), &mut rng, -1..=1);
+            let intervals = pitches
+                .windows(2)
+                .map(|w| (w[1] - w[0]).abs())
+                .collect::<Vec<i32>>();
+            let count = |f: fn(i32) -> bool| intervals.iter().filter(|&&i| f(i)).count();
+
+            let (steps, leaps) = (count(|i| i == 1 || i == 2), count(|i| i >= LEAP));
+            assert!(
+                steps > leaps * 2,
+                "seed {}: {} steps, {} leaps",
+                seed,
+                steps,
+                leaps
+            );
+            assert!(intervals.iter().all(|&i| i < MOTION.len() as i32));
+        }
+    }
+
+    #[test]
+    fn range() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let pitches = melody(&Melody::new(2), &mut rng, 0..=1);
+        assert!(pitches.iter().all(|pitch| (0..14).contains(pitch)));
+        assert_eq!(0, *pitches.iter().min().unwrap());
+        assert_eq!(13, *pitches.iter().max().unwrap());
+
+        let scale = Scale::new(0, &MODES[0]);
+        let pitch = Melody::new(2).next(&mut rng, &scale, &(0..=0), [Some(30), Some(29)]);
+        assert_eq!(6, pitch);
+    }
+
+    #[test]
+    fn recovery() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let pitches = melody(&Melody::new(0), &mut rng, -2..=2);
+        let (mut opposite, mut same) = (0, 0);
+        for w in pitches.windows(3) {
+            let (leap, next) = (w[1] - w[0], w[2] - w[1]);
+            if leap.abs() >= LEAP && next != 0 {
+                if next.signum() == leap.signum() {
+                    same += 1;
+                } else {
+                    opposite += 1;
+                }
+            }
+        }
+        assert!(opposite > same * 2, "{} <= {} * 2", opposite, same);
+    }
+
+    #[test]
+    fn train() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let mut trained = Melody::new(2);
+        trained.train(&[0, 2, 4].repeat(100));
+        let pitches = melody(&trained, &mut rng, 0..=0);
+        let degrees = pitches.windows(3).filter(|w| w[0] == 0 && w[1] == 2);
+        let (count, matches) = degrees.fold((0, 0), |(count, matches), w| {
+            (count + 1, matches + (w[2] == 4) as usize)
+        });
+        assert!(count > 100);
+        assert!(matches * 10 > count * 9, "{} / {}", matches, count);
+
+        let mut first = Melody::new(1);
+        first.train(&[3, 5].repeat(100));
+        let pitches = melody(&first, &mut rng, 0..=0);
+        let steps = pitches.windows(2).filter(|w| w[0] == 3);
+        let (count, matches) = steps.fold((0, 0), |(count, matches), w| {
+            (count + 1, matches + (w[1] == 5) as usize)
+        });
+        assert!(matches * 10 > count * 9, "{} / {}", matches, count);
+    }
+
+    #[test]
+    fn restore() {
+        let mut trained = Melody::new(2);
+        trained.train(&[0, 2, 4].repeat(100));
+
+        let mut restored = Melody::new(2);
+        restored.restore(&trained.snapshot());
+        assert_eq!(trained.snapshot(), restored.snapshot());
+        assert_eq!(
+            melody(&trained, &mut SmallRng::seed_from_u64(0), 0..=0),
+            melody(&restored, &mut SmallRng::seed_from_u64(0), 0..=0)
+        );
+    }
+}
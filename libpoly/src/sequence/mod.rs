@@ -1,65 +1,211 @@
+mod melody;
 mod note;
 
 use core::fmt;
 
 use rand::{rngs::SmallRng, seq::SliceRandom, Rng};
 
-use crate::clock::Clock;
-use crate::constants::{Scale, LENGTH, METRES, SCALES, SEQUENCE_MUTATE, TEMPOS};
+use core::ops::RangeInclusive;
+
+use crate::clock::{interval, Clock};
+use crate::config::PolyConfig;
+use crate::constants::{DEGREES, DIVISIONS, LENGTH, ORDER, PPQ};
 use crate::error::Error;
 use crate::logger::{debug, error};
+use crate::score::Score;
+use crate::snapshot::{NoteState, SequenceState};
+use crate::theory::Scale;
+use crate::track::Part;
+use melody::Melody;
 use note::Note;
 
 pub struct Sequence {
-    tempo: usize,
-    scale: &'static Scale,
+    part: &'static Part,
+    melody: Melody,
     notes: [Note; LENGTH],
     length: usize,
+    padding: usize,
+    bar: usize,
+    locked: bool,
+    rate: f32,
+    mutate: RangeInclusive<usize>,
     mutate_clock: Clock,
     note_clock: Clock,
     note: usize,
+    position: usize,
 }
 
 impl Sequence {
-    pub fn new(rng: &mut SmallRng, sample_rate: f32) -> Result<Self, Error> {
-        let tempo = ((60.0 / rng.gen_range(TEMPOS)) * sample_rate) as usize;
-        let scale = SCALES.choose(rng).ok_or(Error::Rng)?;
-
-        let length = *METRES.choose(rng).ok_or(Error::Rng)?;
+    pub fn new(
+        rng: &mut SmallRng,
+        score: &Score,
+        part: &'static Part,
+        config: &PolyConfig,
+    ) -> Result<Self, Error> {
+        let length = *config.metres.choose(rng).ok_or(Error::Rng)?;
+        let melody = Melody::new(ORDER);
 
         let mut notes = [Note::default(); LENGTH];
-        for i in 0..length {
-            notes[i] = Note::new(rng, scale, length)?;
+        let mut offset = 0;
+        for index in 0..length {
+            let strong = (offset * part.stretch).is_multiple_of(PPQ);
+            let history = history(notes[..index].iter().rev(), &score.scale);
+            notes[index] = Note::new(rng, &score.scale, &part.octaves, strong, &melody, history)?;
+            offset += notes[index].ticks;
         }
 
-        let mutate_clock = Clock::deadline(rng.gen_range(SEQUENCE_MUTATE));
+        let mutate_clock = Clock::deadline(rng.gen_range(config.sequence_mutate.clone()));
         let note_clock = Clock::default();
 
-        Ok(Sequence {
-            tempo,
-            scale,
+        let mut sequence = Sequence {
+            part,
+            melody,
             notes,
             length,
+            padding: 0,
+            bar: 0,
+            locked: false,
+            rate: 1.0,
+            mutate: config.sequence_mutate.clone(),
             mutate_clock,
             note_clock,
             note: length - 1,
-        })
+            position: 0,
+        };
+        sequence.pad(score.bar());
+        Ok(sequence)
+    }
+
+    pub fn train(&mut self, degrees: &[usize]) {
+        self.melody.train(degrees);
+    }
+
+    pub fn set_locked(&mut self, locked: bool) {
+        self.locked = locked;
+    }
+
+    pub fn set_rate(&mut self, rate: f32) {
+        self.mutate_clock.scale(self.rate / rate);
+        self.rate = rate;
+    }
+
+    pub fn snapshot(&self) -> SequenceState {
+        SequenceState {
+            notes: self.notes.map(|note| NoteState {
+                ticks: note.ticks,
+                degree: note.degree,
+                chord: note.chord,
+                octave: note.octave,
+                rest: note.rest,
+                tie: note.tie,
+            }),
+            length: self.length,
+            padding: self.padding,
+            bar: self.bar,
+            note: self.note,
+            position: self.position,
+            locked: self.locked,
+            rate: self.rate,
+            mutate_clock: self.mutate_clock.snapshot(),
+            note_clock: self.note_clock.snapshot(),
+            melody: self.melody.snapshot(),
+        }
+    }
+
+    pub fn restore(&mut self, state: &SequenceState) -> Result<(), Error> {
+        if state.length == 0 || state.length > LENGTH || state.note >= state.length {
+            return Err(Error::Snapshot);
+        }
+
+        let ticks = 1..=DIVISIONS[DIVISIONS.len() - 1];
+        let valid = state.notes[..state.length]
+            .iter()
+            .enumerate()
+            .all(|(index, note)| {
+                let padding = if index == state.length - 1 {
+                    state.padding
+                } else {
+                    0
+                };
+                note.ticks
+                    .checked_sub(padding)
+                    .is_some_and(|length| ticks.contains(&length))
+                    && note.degree < DEGREES
+                    && self.part.octaves.contains(&note.octave)
+            });
+        if !valid || state.padding >= state.bar || !(state.rate > 0.0 && state.rate.is_finite()) {
+            return Err(Error::Snapshot);
+        }
+
+        self.notes = state.notes.map(|note| Note {
+            ticks: note.ticks,
+            degree: note.degree,
+            chord: note.chord,
+            octave: note.octave,
+            rest: note.rest,
+            tie: note.tie,
+        });
+        self.length = state.length;
+        self.padding = state.padding;
+        self.bar = state.bar;
+        self.note = state.note;
+        self.position = state.position;
+        self.locked = state.locked;
+        self.rate = state.rate;
+        self.mutate_clock.restore(&state.mutate_clock);
+        self.note_clock.restore(&state.note_clock);
+        self.melody.restore(&state.melody);
+        Ok(())
     }
 
-    pub fn next(&mut self, rng: &mut SmallRng) -> Option<(usize, f32)> {
+    pub fn locate(&mut self, score: &Score, tick: usize) {
+        let notes = &self.notes[..self.length];
+        let total = notes.iter().map(|note| note.ticks).sum::<usize>() * self.part.stretch;
+
+        let mut position = tick / total * total;
+        let mut index = self.length - 1;
+        for (next, note) in notes.iter().enumerate() {
+            if position >= tick {
+                break;
+            }
+            position += note.ticks * self.part.stretch;
+            index = next;
+        }
+
+        self.position = position;
+        self.note = index;
+        self.note_clock.reset(score.cue(position));
+    }
+
+    pub fn next(&mut self, rng: &mut SmallRng, score: &Score) -> Option<(usize, f32)> {
         if self.mutate_clock.tick() {
-            self.mutate(rng);
+            self.mutate(rng, score);
         }
 
         if self.note_clock.tick() {
-            Some(self.step())
+            self.step(score)
         } else {
             None
         }
     }
 
-    fn mutate(&mut self, rng: &mut SmallRng) {
-        self.mutate_clock.reset(rng.gen_range(SEQUENCE_MUTATE));
+    pub fn remaining(&self) -> usize {
+        self.mutate_clock
+            .remaining()
+            .min(self.note_clock.remaining())
+    }
+
+    pub fn skip(&mut self, samples: usize) {
+        self.mutate_clock.advance(samples);
+        self.note_clock.advance(samples);
+    }
+
+    fn mutate(&mut self, rng: &mut SmallRng, score: &Score) {
+        self.mutate_clock
+            .reset(interval(rng.gen_range(self.mutate.clone()), self.rate));
+        if self.locked {
+            return;
+        }
 
         let mut index;
         loop {
@@ -69,7 +215,28 @@ impl Sequence {
             }
         }
 
-        if let Ok(note) = Note::new(rng, self.scale, self.length) {
+        let offset = self.notes[..index]
+            .iter()
+            .map(|note| note.ticks)
+            .sum::<usize>();
+        let strong = (offset * self.part.stretch).is_multiple_of(PPQ);
+        let history = history(
+            self.notes[..index]
+                .iter()
+                .rev()
+                .chain(self.notes[index + 1..self.length].iter().rev()),
+            &score.scale,
+        );
+
+        if let Ok(mut note) = Note::new(
+            rng,
+            &score.scale,
+            &self.part.octaves,
+            strong,
+            &self.melody,
+            history,
+        ) {
+            note.ticks = self.notes[index].ticks;
             debug!("[NOTE {}] {}", index, note);
             self.notes[index] = note;
         } else {
@@ -77,18 +244,73 @@ impl Sequence {
         }
     }
 
-    fn step(&mut self) -> (usize, f32) {
+    fn step(&mut self, score: &Score) -> Option<(usize, f32)> {
+        let previous = self.notes[self.note];
+
         self.note += 1;
         if self.note >= self.length {
             self.note = 0;
         }
+        if self.note == self.length - 1 && self.bar != score.bar() {
+            self.pad(score.bar());
+        }
+
+        let note = self.notes[self.note];
+        let start = self.position;
+        self.position += note.ticks * self.part.stretch;
+        self.note_clock.reset(score.until(self.position));
+
+        if note.rest || previous.tie {
+            return None;
+        }
+
+        let degree = if note.chord {
+            score.chord(start, note.degree)
+        } else {
+            note.degree
+        };
+
+        let mut end = self.position;
+        let mut index = self.note;
+        for _ in 1..self.length {
+            if !self.notes[index].tie {
+                break;
+            }
+            index = (index + 1) % self.length;
+            if self.notes[index].rest {
+                break;
+            }
+            end += self.notes[index].ticks * self.part.stretch;
+        }
+
+        Some((score.until(end), score.frequency(degree, note.octave)?))
+    }
 
-        let length = self.notes[self.note].length * self.tempo;
+    fn pad(&mut self, bar: usize) {
+        self.notes[self.length - 1].ticks -= self.padding;
+        let total = self.notes[..self.length]
+            .iter()
+            .map(|note| note.ticks)
+            .sum::<usize>();
 
-        self.note_clock.reset(length);
+        self.padding = (bar - total % bar) % bar;
+        self.notes[self.length - 1].ticks += self.padding;
+        self.bar = bar;
+    }
+}
 
-        (length, self.notes[self.note].frequency)
+fn history<'a, I>(notes: I, scale: &Scale) -> [Option<i32>; 2]
+where
+    I: Iterator<Item = &'a Note>,
+{
+    let mut history = [None; 2];
+    for (slot, pitch) in history
+        .iter_mut()
+        .zip(notes.filter_map(|note| note.pitch(scale)))
+    {
+        *slot = Some(pitch);
     }
+    history
 }
 
 impl fmt::Display for Sequence {
@@ -107,32 +329,60 @@ impl fmt::Display for Sequence {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::constants::SCALES;
+    use crate::constants::{MODES, SEQUENCE_MUTATE};
+    use crate::theory::Scale;
 
     use rand::SeedableRng;
 
-    #[test]
-    fn next() {
-        let mut rng = SmallRng::seed_from_u64(0);
+    const PART: Part = Part {
+        octaves: 0..=3,
+        stretch: 1,
+        level: 1.0,
+    };
 
-        let mut sequence = Sequence {
-            tempo: 1,
-            scale: &SCALES[0],
+    fn score() -> Score {
+        let mut score = Score::default();
+        score.scale = Scale::new(9, &MODES[5]);
+        score
+    }
+
+    fn play(
+        sequence: &mut Sequence,
+        rng: &mut SmallRng,
+        score: &mut Score,
+        frames: u64,
+    ) -> Vec<Option<(usize, f32)>> {
+        (0..frames)
+            .map(|frame| {
+                score.frame = frame;
+                sequence.next(rng, score)
+            })
+            .collect()
+    }
+
+    fn sequence() -> Sequence {
+        Sequence {
+            part: &PART,
+            melody: Melody::new(2),
             notes: [
                 Note {
-                    length: 1,
+                    ticks: 1,
+                    octave: 0,
                     ..Default::default()
                 },
                 Note {
-                    length: 2,
+                    ticks: 2,
+                    octave: 1,
                     ..Default::default()
                 },
                 Note {
-                    length: 3,
+                    ticks: 3,
+                    octave: 2,
                     ..Default::default()
                 },
                 Note {
-                    length: 4,
+                    ticks: 4,
+                    octave: 3,
                     ..Default::default()
                 },
                 Note::default(),
@@ -141,28 +391,286 @@ mod tests {
                 Note::default(),
             ],
             length: 4,
+            padding: 0,
+            bar: PPQ * 4,
+            locked: false,
+            rate: 1.0,
+            mutate: SEQUENCE_MUTATE,
             mutate_clock: Clock::deadline(1000),
             note_clock: Clock::default(),
             note: 3,
-        };
+            position: 0,
+        }
+    }
+
+    #[test]
+    fn next() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut sequence = sequence();
+        let mut score = score();
 
         assert_eq!(
-            (0..11)
-                .map(|_| sequence.next(&mut rng))
-                .collect::<Vec<Option<(usize, f32)>>>(),
+            play(&mut sequence, &mut rng, &mut score, 11),
             vec![
-                Some((1, 0.0)),
-                Some((2, 0.0)),
+                Some((1, 440.0)),
+                Some((2, 880.0)),
                 None,
-                Some((3, 0.0)),
+                Some((3, 1760.0)),
                 None,
                 None,
-                Some((4, 0.0)),
+                Some((4, 3520.0)),
                 None,
                 None,
                 None,
-                Some((1, 0.0)),
+                Some((1, 440.0)),
             ]
         )
     }
+
+    #[test]
+    fn skip() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut sequence = sequence();
+        let mut score = score();
+
+        assert_eq!(Some((1, 440.0)), sequence.next(&mut rng, &score));
+        score.frame = 1;
+        assert_eq!(Some((2, 880.0)), sequence.next(&mut rng, &score));
+        assert_eq!(2, sequence.remaining());
+
+        sequence.skip(1);
+        score.frame = 3;
+        assert_eq!(Some((3, 1760.0)), sequence.next(&mut rng, &score));
+        assert_eq!(3, sequence.remaining());
+
+        sequence.skip(2);
+        score.frame = 6;
+        assert_eq!(Some((4, 3520.0)), sequence.next(&mut rng, &score));
+    }
+
+    #[test]
+    fn rest() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut sequence = sequence();
+        sequence.notes[1].rest = true;
+        let mut score = score();
+
+        assert_eq!(
+            play(&mut sequence, &mut rng, &mut score, 6),
+            vec![Some((1, 440.0)), None, None, Some((3, 1760.0)), None, None]
+        );
+    }
+
+    #[test]
+    fn tie() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut sequence = sequence();
+        sequence.notes[0].tie = true;
+        sequence.notes[1].tie = true;
+        let mut score = score();
+
+        assert_eq!(
+            play(&mut sequence, &mut rng, &mut score, 10),
+            vec![
+                Some((6, 440.0)),
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some((4, 3520.0)),
+                None,
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn swing() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut sequence = sequence();
+        sequence.length = 2;
+        sequence.note = 1;
+        sequence.notes[0].ticks = PPQ / 2;
+        sequence.notes[1].ticks = PPQ / 2;
+        let mut score = score();
+        score.swing = 0.25;
+
+        let lengths = play(&mut sequence, &mut rng, &mut score, PPQ as u64 * 2)
+            .into_iter()
+            .flatten()
+            .map(|(length, _)| length)
+            .collect::<Vec<usize>>();
+        assert_eq!(vec![60, 36, 60, 36], lengths);
+    }
+
+    #[test]
+    fn chord() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut sequence = sequence();
+        sequence.length = 1;
+        sequence.note = 0;
+        sequence.mutate_clock = Clock::deadline(usize::MAX);
+        sequence.notes[0] = Note {
+            ticks: PPQ * 4,
+            degree: 1,
+            chord: true,
+            ..Default::default()
+        };
+        let mut score = score();
+
+        let frequencies = play(&mut sequence, &mut rng, &mut score, PPQ as u64 * 16)
+            .into_iter()
+            .flatten()
+            .map(|(_, frequency)| Some(frequency))
+            .collect::<Vec<Option<f32>>>();
+        assert_eq!(
+            vec![
+                score.frequency(2, 0),
+                score.frequency(5, 0),
+                score.frequency(6, 0),
+                score.frequency(2, 0),
+            ],
+            frequencies
+        );
+    }
+
+    #[test]
+    fn history() {
+        let scale = Scale::new(0, &MODES[0]);
+        let mut sequence = sequence();
+        assert_eq!(
+            [Some(14), Some(7)],
+            super::history(sequence.notes[..3].iter().rev(), &scale)
+        );
+
+        sequence.notes[1].rest = true;
+        assert_eq!(
+            [Some(14), Some(0)],
+            super::history(sequence.notes[..3].iter().rev(), &scale)
+        );
+        assert_eq!(
+            [Some(0), None],
+            super::history(sequence.notes[..1].iter().rev(), &scale)
+        );
+    }
+
+    #[test]
+    fn locked() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut sequence = sequence();
+        sequence.set_locked(true);
+        sequence.mutate_clock = Clock::deadline(1);
+        let expected = sequence.snapshot().notes;
+        let mut score = score();
+
+        play(&mut sequence, &mut rng, &mut score, 10);
+        assert_eq!(expected, sequence.snapshot().notes);
+        assert!(!sequence.mutate_clock.ready());
+    }
+
+    #[test]
+    fn set_rate() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut sequence = sequence();
+        sequence.set_rate(10.0);
+        assert_eq!(100, sequence.mutate_clock.remaining());
+
+        let mut score = score();
+        play(&mut sequence, &mut rng, &mut score, 100);
+        assert!(sequence.mutate_clock.remaining() <= *SEQUENCE_MUTATE.end() / 10);
+    }
+
+    #[test]
+    fn restore() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut sequence = sequence();
+        let mut score = score();
+        sequence.train(&[0, 2, 4]);
+        play(&mut sequence, &mut rng, &mut score, 5);
+        let state = sequence.snapshot();
+
+        let mut restored = Sequence::new(&mut rng, &score, &PART, &PolyConfig::default()).unwrap();
+        restored.restore(&state).unwrap();
+        assert_eq!(state, restored.snapshot());
+        assert_eq!(format!("{}", sequence), format!("{}", restored));
+
+        let mut invalid = state;
+        invalid.note = invalid.length;
+        assert!(restored.restore(&invalid).is_err());
+
+        let mut invalid = state;
+        invalid.notes[1].degree = usize::MAX;
+        assert!(restored.restore(&invalid).is_err());
+
+        let mut invalid = state;
+        invalid.notes[2].ticks = 0;
+        assert!(restored.restore(&invalid).is_err());
+
+        let mut invalid = state;
+        invalid.notes[0].octave = 4;
+        assert!(restored.restore(&invalid).is_err());
+
+        let mut invalid = state;
+        invalid.padding = invalid.notes[3].ticks;
+        assert!(restored.restore(&invalid).is_err());
+    }
+
+    #[test]
+    fn locate() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut sequence = sequence();
+        let mut score = score();
+
+        for (tick, expected) in [(0, (1, 440.0)), (13, (3, 1760.0)), (21, (2, 880.0))] {
+            score.frame = tick as u64;
+            sequence.locate(&score, tick);
+            assert_eq!(Some(expected), sequence.next(&mut rng, &score));
+        }
+
+        score.frame = 25;
+        sequence.locate(&score, 24);
+        assert_eq!(None, sequence.next(&mut rng, &score));
+        score.frame = 26;
+        assert_eq!(Some((4, 3520.0)), sequence.next(&mut rng, &score));
+    }
+
+    #[test]
+    fn bars() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let score = score();
+
+        for _ in 0..100 {
+            let sequence = Sequence::new(&mut rng, &score, &PART, &PolyConfig::default()).unwrap();
+            let total = sequence.notes[..sequence.length]
+                .iter()
+                .map(|note| note.ticks)
+                .sum::<usize>();
+            assert_eq!(0, total % score.bar());
+        }
+    }
+
+    #[test]
+    fn metre() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut score = score();
+
+        for beats in [3, 5, 4] {
+            let mut sequence =
+                Sequence::new(&mut rng, &score, &PART, &PolyConfig::default()).unwrap();
+            let total = |sequence: &Sequence| {
+                sequence.notes[..sequence.length]
+                    .iter()
+                    .map(|note| note.ticks)
+                    .sum::<usize>()
+            };
+            let unpadded = total(&sequence) - sequence.padding;
+
+            score.beats = beats;
+            play(&mut sequence, &mut rng, &mut score, 4000);
+            assert_eq!(0, total(&sequence) % score.bar());
+            assert_eq!(unpadded, total(&sequence) - sequence.padding);
+        }
+    }
 }
@@ -1,37 +1,80 @@
 use core::fmt;
+use core::ops::RangeInclusive;
 
 use rand::{rngs::SmallRng, seq::SliceRandom, Rng};
 
-use crate::constants::Scale;
+use super::melody::Melody;
+use crate::constants::{CHORD, DIVISIONS, REST, TIE};
 use crate::error::Error;
+use crate::theory::Scale;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Default)]
 pub struct Note {
-    pub length: usize,
-    pub frequency: f32,
+    pub ticks: usize,
+    pub degree: usize,
+    pub chord: bool,
+    pub octave: i32,
+    pub rest: bool,
+    pub tie: bool,
 }
 
 impl Note {
-    pub fn new(rng: &mut SmallRng, scale: &Scale, length: usize) -> Result<Self, Error> {
-        let length = rng.gen_range(1..=length);
-        let frequency = *scale.choose(rng).ok_or(Error::Rng)?;
+    pub fn new(
+        rng: &mut SmallRng,
+        scale: &Scale,
+        octaves: &RangeInclusive<i32>,
+        strong: bool,
+        melody: &Melody,
+        history: [Option<i32>; 2],
+    ) -> Result<Self, Error> {
+        let ticks = *DIVISIONS.choose(rng).ok_or(Error::Rng)?;
+        let chord = strong && rng.gen_bool(CHORD);
+        let (degree, octave) = if chord {
+            (rng.gen_range(0..3), rng.gen_range(octaves.clone()))
+        } else {
+            let pitch = melody.next(rng, scale, octaves, history);
+            let length = scale.len() as i32;
+            (pitch.rem_euclid(length) as usize, pitch.div_euclid(length))
+        };
+        let rest = rng.gen_bool(REST);
+        let tie = !rest && rng.gen_bool(TIE);
 
-        Ok(Note { length, frequency })
+        Ok(Note {
+            ticks,
+            degree,
+            chord,
+            octave,
+            rest,
+            tie,
+        })
     }
 }
 
-impl Default for Note {
-    fn default() -> Self {
-        Note {
-            length: 0,
-            frequency: 0.0,
+impl Note {
+    pub fn pitch(&self, scale: &Scale) -> Option<i32> {
+        if self.rest || self.chord {
+            None
+        } else {
+            Some(self.octave * scale.len() as i32 + self.degree as i32)
         }
     }
 }
 
 impl fmt::Display for Note {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "({}, {:.2}Hz)", self.length, self.frequency)
+        if self.rest {
+            write!(f, "({}, Rest)", self.ticks)
+        } else {
+            write!(
+                f,
+                "({}, {}{}, {}{})",
+                self.ticks,
+                self.degree,
+                if self.chord { "*" } else { "" },
+                self.octave,
+                if self.tie { "~" } else { "" }
+            )
+        }
     }
 }
 
@@ -40,29 +83,127 @@ mod tests {
     use rand::SeedableRng;
 
     use super::*;
+    use crate::constants::MODES;
 
     #[test]
     fn new() {
         let mut rng = SmallRng::seed_from_u64(0);
 
-        let result = Note::new(&mut rng, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0], 4);
+        let result = Note::new(
+            &mut rng,
+            &Scale::new(0, &MODES[0]),
+            &(0..=0),
+            false,
+            &Melody::new(2),
+            [None; 2],
+        );
         assert!(result.is_ok());
 
         let note = result.unwrap();
-        assert_eq!(4.0, note.frequency);
-        assert_eq!(3, note.length);
+        assert_eq!(64, note.ticks);
+        assert_eq!(2, note.degree);
+        assert_eq!(0, note.octave);
+        assert!(!note.rest);
+        assert!(!note.tie);
+    }
+
+    #[test]
+    fn divisions() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let scale = Scale::new(0, &MODES[0]);
+        let notes = (0..1000)
+            .map(|_| {
+                Note::new(
+                    &mut rng,
+                    &scale,
+                    &(0..=0),
+                    false,
+                    &Melody::new(2),
+                    [None; 2],
+                )
+                .unwrap()
+            })
+            .collect::<Vec<Note>>();
+        assert!(notes.iter().all(|note| DIVISIONS.contains(&note.ticks)));
+        assert!(notes.iter().any(|note| note.rest));
+        assert!(notes.iter().any(|note| note.tie));
+        assert!(notes.iter().all(|note| !(note.rest && note.tie)));
+        assert!(notes.iter().all(|note| !note.chord));
+    }
+
+    #[test]
+    fn chord() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let scale = Scale::new(0, &MODES[0]);
+        let notes = (0..1000)
+            .map(|_| {
+                Note::new(&mut rng, &scale, &(0..=0), true, &Melody::new(2), [None; 2]).unwrap()
+            })
+            .collect::<Vec<Note>>();
+        let chords = notes.iter().filter(|note| note.chord).count();
+        assert!((600..800).contains(&chords));
+        assert!(notes
+            .iter()
+            .filter(|note| note.chord)
+            .all(|note| note.degree < 3));
+    }
+
+    #[test]
+    fn range() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let scale = Scale::new(0, &MODES[8]);
+        for _ in 0..100 {
+            let note = Note::new(
+                &mut rng,
+                &scale,
+                &(-2..=1),
+                false,
+                &Melody::new(2),
+                [None; 2],
+            )
+            .unwrap();
+            assert!(note.degree < 5);
+            assert!((-2..=1).contains(&note.octave));
+        }
+    }
+
+    #[test]
+    fn pitch() {
+        let scale = Scale::new(0, &MODES[8]);
+        let mut note = Note {
+            degree: 3,
+            octave: -1,
+            ..Default::default()
+        };
+        assert_eq!(Some(-2), note.pitch(&scale));
+
+        note.chord = true;
+        assert_eq!(None, note.pitch(&scale));
     }
 
     #[test]
     fn default() {
         let note = Note::default();
-        assert_eq!(0.0, note.frequency);
-        assert_eq!(0, note.length);
+        assert_eq!(0, note.degree);
+        assert_eq!(0, note.octave);
+        assert_eq!(0, note.ticks);
     }
 
     #[test]
     fn display() {
-        let note = Note::default();
-        assert_eq!("(0, 0.00Hz)", format!("{}", note));
+        let mut note = Note::default();
+        assert_eq!("(0, 0, 0)", format!("{}", note));
+
+        note.tie = true;
+        assert_eq!("(0, 0, 0~)", format!("{}", note));
+
+        note.chord = true;
+        assert_eq!("(0, 0*, 0~)", format!("{}", note));
+
+        note.rest = true;
+        assert_eq!("(0, Rest)", format!("{}", note));
     }
 }
@@ -0,0 +1,460 @@
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{DEGREES, LENGTH, LFOS, ROUTES, SNAPSHOT, TRACKS};
+use crate::error::Error;
+
+const MAGIC: [u8; 4] = *b"POLY";
+const VERSION: u8 = 11;
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Snapshot {
+    pub(crate) seed: u64,
+    pub(crate) frame: u64,
+    pub(crate) score: ScoreState,
+    pub(crate) tracks: [TrackState; TRACKS.len()],
+    pub(crate) effects: EffectsState,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct ScoreState {
+    pub sample_rate: f32,
+    pub bpm: f32,
+    pub target: f32,
+    pub swing: f32,
+    pub beats: usize,
+    pub scale: ScaleState,
+    pub progression: usize,
+    pub changes: usize,
+    pub origin_tick: f64,
+    pub origin_frame: f64,
+    pub cycle: usize,
+    pub pivot: Option<usize>,
+    pub key: Option<ScaleState>,
+    pub metre: Option<usize>,
+    pub locked: bool,
+    pub rate: f32,
+    pub clocks: [ClockState; 4],
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct ScaleState {
+    pub root: i32,
+    pub mode: usize,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct ClockState {
+    pub tick: usize,
+    pub deadline: usize,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct TrackState {
+    pub seed: u64,
+    pub sequence: SequenceState,
+    pub synth: SynthState,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct SequenceState {
+    pub notes: [NoteState; LENGTH],
+    pub length: usize,
+    pub padding: usize,
+    pub bar: usize,
+    pub note: usize,
+    pub position: usize,
+    pub locked: bool,
+    pub rate: f32,
+    pub mutate_clock: ClockState,
+    pub note_clock: ClockState,
+    pub melody: MelodyState,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct MelodyState {
+    pub first: [[u32; DEGREES]; DEGREES],
+    pub second: [[[u32; DEGREES]; DEGREES]; DEGREES],
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct NoteState {
+    pub ticks: usize,
+    pub degree: usize,
+    pub chord: bool,
+    pub octave: i32,
+    pub rest: bool,
+    pub tie: bool,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct SynthState {
+    pub voice: VoiceState,
+    pub age: usize,
+    pub legato: bool,
+    pub locked: bool,
+    pub rate: f32,
+    pub mutate_clock: ClockState,
+    pub lfos: [LfoState; LFOS],
+    pub routes: [RouteState; ROUTES],
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct VoiceState {
+    pub algorithm: usize,
+    pub indices: [[f32; 3]; 3],
+    pub feedback: f32,
+    pub operators: [OperatorState; 3],
+    pub envelopes: [EnvelopeState; 3],
+    pub filter: FilterState,
+    pub pan: PanState,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct OperatorState {
+    pub signal: u8,
+    pub oscillator: u8,
+    pub ratio: f32,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct EnvelopeState {
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+    pub curve: f32,
+    pub timing: u8,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct FilterState {
+    pub response: u8,
+    pub cutoff: f32,
+    pub q: f32,
+    pub envelope: EnvelopeState,
+    pub depth: f32,
+    pub tracking: f32,
+    pub sensitivity: f32,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct PanState {
+    pub depth: f32,
+    pub rate: f32,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct LfoState {
+    pub shape: u8,
+    pub rate: f32,
+    pub sync: usize,
+    pub seed: u64,
+    pub phase: f32,
+    pub from: f32,
+    pub to: f32,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct RouteState {
+    pub source: u8,
+    pub destination: u8,
+    pub depth: f32,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct EffectsState {
+    pub delay: DelayState,
+    pub reverb: ReverbState,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct DelayState {
+    pub ticks: usize,
+    pub feedback: f32,
+    pub mix: f32,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct ReverbState {
+    pub room: f32,
+    pub damp: f32,
+    pub mix: f32,
+}
+
+impl Snapshot {
+    pub const SIZE: usize = SNAPSHOT;
+
+    pub fn encode(&self, out: &mut [u8]) -> Result<usize, Error> {
+        let mut writer = Writer {
+            buffer: out,
+            position: 0,
+        };
+        MAGIC.encode(&mut writer)?;
+        VERSION.encode(&mut writer)?;
+        self.seed.encode(&mut writer)?;
+        self.frame.encode(&mut writer)?;
+        self.score.encode(&mut writer)?;
+        self.tracks.encode(&mut writer)?;
+        self.effects.encode(&mut writer)?;
+        Ok(writer.position)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader {
+            buffer: bytes,
+            position: 0,
+        };
+        if <[u8; 4]>::decode(&mut reader)? != MAGIC || u8::decode(&mut reader)? != VERSION {
+            return Err(Error::Snapshot);
+        }
+
+        Ok(Snapshot {
+            seed: Encode::decode(&mut reader)?,
+            frame: Encode::decode(&mut reader)?,
+            score: Encode::decode(&mut reader)?,
+            tracks: Encode::decode(&mut reader)?,
+            effects: Encode::decode(&mut reader)?,
+        })
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[FRAME] {} [SEED] {}", self.frame, self.seed)
+    }
+}
+
+struct Writer<'a> {
+    buffer: &'a mut [u8],
+    position: usize,
+}
+
+impl Writer<'_> {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let end = self.position + bytes.len();
+        self.buffer
+            .get_mut(self.position..end)
+            .ok_or(Error::Snapshot)?
+            .copy_from_slice(bytes);
+        self.position = end;
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    buffer: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn read<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let end = self.position + N;
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(self.buffer.get(self.position..end).ok_or(Error::Snapshot)?);
+        self.position = end;
+        Ok(bytes)
+    }
+}
+
+trait Encode: Sized {
+    fn encode(&self, writer: &mut Writer) -> Result<(), Error>;
+    fn decode(reader: &mut Reader) -> Result<Self, Error>;
+}
+
+macro_rules! primitive {
+    ($($type:ty),*) => {$(
+        impl Encode for $type {
+            fn encode(&self, writer: &mut Writer) -> Result<(), Error> {
+                writer.write(&self.to_le_bytes())
+            }
+
+            fn decode(reader: &mut Reader) -> Result<Self, Error> {
+                Ok(<$type>::from_le_bytes(reader.read()?))
+            }
+        }
+    )*};
+}
+
+primitive!(u8, u32, u64, i32, f32, f64);
+
+impl Encode for usize {
+    fn encode(&self, writer: &mut Writer) -> Result<(), Error> {
+        (*self as u64).encode(writer)
+    }
+
+    fn decode(reader: &mut Reader) -> Result<Self, Error> {
+        Ok(usize::try_from(u64::decode(reader)?).unwrap_or(usize::MAX))
+    }
+}
+
+impl Encode for bool {
+    fn encode(&self, writer: &mut Writer) -> Result<(), Error> {
+        (*self as u8).encode(writer)
+    }
+
+    fn decode(reader: &mut Reader) -> Result<Self, Error> {
+        match u8::decode(reader)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::Snapshot),
+        }
+    }
+}
+
+impl<T: Encode + Copy + Default> Encode for Option<T> {
+    fn encode(&self, writer: &mut Writer) -> Result<(), Error> {
+        self.is_some().encode(writer)?;
+        self.unwrap_or_default().encode(writer)
+    }
+
+    fn decode(reader: &mut Reader) -> Result<Self, Error> {
+        let some = bool::decode(reader)?;
+        let value = T::decode(reader)?;
+        Ok(some.then_some(value))
+    }
+}
+
+impl<T: Encode + Copy + Default, const N: usize> Encode for [T; N] {
+    fn encode(&self, writer: &mut Writer) -> Result<(), Error> {
+        for item in self {
+            item.encode(writer)?;
+        }
+        Ok(())
+    }
+
+    fn decode(reader: &mut Reader) -> Result<Self, Error> {
+        let mut array = [T::default(); N];
+        for item in &mut array {
+            *item = T::decode(reader)?;
+        }
+        Ok(array)
+    }
+}
+
+macro_rules! state {
+    ($($type:ident { $($field:ident),* })*) => {$(
+        impl Encode for $type {
+            fn encode(&self, writer: &mut Writer) -> Result<(), Error> {
+                $(self.$field.encode(writer)?;)*
+                Ok(())
+            }
+
+            fn decode(reader: &mut Reader) -> Result<Self, Error> {
+                Ok($type {
+                    $($field: Encode::decode(reader)?,)*
+                })
+            }
+        }
+    )*};
+}
+
+state! {
+    ScoreState {
+        sample_rate, bpm, target, swing, beats, scale, progression, changes,
+        origin_tick, origin_frame, cycle, pivot, key, metre, locked, rate, clocks
+    }
+    ScaleState { root, mode }
+    ClockState { tick, deadline }
+    TrackState { seed, sequence, synth }
+    SequenceState {
+        notes, length, padding, bar, note, position, locked, rate, mutate_clock, note_clock, melody
+    }
+    MelodyState { first, second }
+    NoteState { ticks, degree, chord, octave, rest, tie }
+    SynthState { voice, age, legato, locked, rate, mutate_clock, lfos, routes }
+    VoiceState { algorithm, indices, feedback, operators, envelopes, filter, pan }
+    OperatorState { signal, oscillator, ratio }
+    EnvelopeState { attack, decay, sustain, release, curve, timing }
+    FilterState { response, cutoff, q, envelope, depth, tracking, sensitivity }
+    PanState { depth, rate }
+    LfoState { shape, rate, sync, seed, phase, from, to }
+    RouteState { source, destination, depth }
+    EffectsState { delay, reverb }
+    DelayState { ticks, feedback, mix }
+    ReverbState { room, damp, mix }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        let mut snapshot = Snapshot {
+            seed: 7,
+            frame: 44_100,
+            ..Default::default()
+        };
+        snapshot.score.bpm = 92.5;
+        snapshot.score.pivot = Some(usize::MAX);
+        snapshot.score.key = Some(ScaleState { root: 7, mode: 5 });
+        snapshot.tracks[1].sequence.notes[3] = NoteState {
+            ticks: 48,
+            degree: 2,
+            chord: true,
+            octave: -1,
+            rest: false,
+            tie: true,
+        };
+        snapshot.tracks[2].synth.voice.indices[0][2] = 1.5;
+        snapshot.effects.delay.ticks = 36;
+        snapshot.effects.reverb.mix = 0.25;
+        snapshot
+    }
+
+    #[test]
+    fn encode() {
+        let snapshot = snapshot();
+        let mut bytes = [0; Snapshot::SIZE];
+        assert_eq!(Snapshot::SIZE, snapshot.encode(&mut bytes).unwrap());
+        assert_eq!(b"POLY", &bytes[..4]);
+        assert_eq!(snapshot, Snapshot::decode(&bytes).unwrap());
+    }
+
+    #[test]
+    fn errors() {
+        let snapshot = snapshot();
+        let mut bytes = [0; Snapshot::SIZE];
+        assert!(snapshot.encode(&mut bytes[..Snapshot::SIZE - 1]).is_err());
+
+        snapshot.encode(&mut bytes).unwrap();
+        assert!(Snapshot::decode(&bytes[..Snapshot::SIZE - 1]).is_err());
+
+        bytes[4] = VERSION + 1;
+        assert!(Snapshot::decode(&bytes).is_err());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!("[FRAME] 44100 [SEED] 7", format!("{}", snapshot()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let snapshot = snapshot();
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(snapshot, serde_json::from_str(&json).unwrap());
+    }
+}
@@ -1,31 +1,68 @@
 use core::fmt;
 
+use libm::expf;
 use rand::rngs::SmallRng;
 use rand::Rng;
 
 use crate::clock::Clock;
-use crate::constants::{ATTACK, DECAY, RELEASE, SUSTAIN};
+use crate::config::PolyConfig;
+use crate::constants::CURVE;
+use crate::error::Error;
+use crate::snapshot::EnvelopeState;
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Timing {
+    Relative,
+    Absolute,
+}
+
+impl TryFrom<u8> for Timing {
+    type Error = Error;
+
+    fn try_from(timing: u8) -> Result<Self, Error> {
+        match timing {
+            0 => Ok(Timing::Relative),
+            1 => Ok(Timing::Absolute),
+            _ => Err(Error::Snapshot),
+        }
+    }
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Timing::Relative => "Relative",
+                Timing::Absolute => "Absolute",
+            }
+        )
+    }
+}
+
+#[derive(Clone)]
 pub struct ADSR {
+    sample_rate: f32,
     attack: f32,
     decay: f32,
     sustain: f32,
     release: f32,
+    curve: f32,
+    timing: Timing,
     value: f32,
     length: f32,
+    coefficient: f32,
     delta: f32,
     clock: Clock,
+    gate: Clock,
     state: State,
 }
 
 impl ADSR {
-    pub fn new(rng: &mut SmallRng) -> Self {
-        let attack = rng.gen_range(ATTACK);
-        let decay = rng.gen_range(DECAY);
-        let sustain = rng.gen_range(SUSTAIN);
-        let release = rng.gen_range(RELEASE);
-
+    pub fn new(sample_rate: f32, attack: f32, decay: f32, sustain: f32, release: f32) -> Self {
         ADSR {
+            sample_rate,
             attack,
             decay,
             sustain,
@@ -34,61 +71,169 @@ impl ADSR {
         }
     }
 
+    pub fn random(rng: &mut SmallRng, sample_rate: f32, config: &PolyConfig) -> Self {
+        let attack = rng.gen_range(config.attack.clone());
+        let decay = rng.gen_range(config.decay.clone());
+        let sustain = rng.gen_range(config.sustain.clone());
+        let release = rng.gen_range(config.release.clone());
+
+        let mut adsr = Self::new(sample_rate, attack, decay, sustain, release);
+        adsr.set_curve(rng.gen_range(CURVE), Timing::Relative);
+        adsr
+    }
+
+    pub fn restore(sample_rate: f32, state: &EnvelopeState) -> Result<Self, Error> {
+        let mut adsr = Self::new(
+            sample_rate,
+            state.attack,
+            state.decay,
+            state.sustain,
+            state.release,
+        );
+        adsr.set_curve(state.curve, Timing::try_from(state.timing)?);
+        Ok(adsr)
+    }
+
+    pub fn set(&mut self, attack: f32, decay: f32, sustain: f32, release: f32) {
+        self.attack = attack;
+        self.decay = decay;
+        self.sustain = sustain;
+        self.release = release;
+    }
+
+    pub fn set_curve(&mut self, curve: f32, timing: Timing) {
+        self.curve = curve;
+        self.timing = timing;
+    }
+
     pub fn start(&mut self, length: usize) {
-        self.state = State::Attack;
-        self.value = 0.0;
+        self.extend(length);
+        self.enter(State::Attack);
+    }
+
+    pub fn extend(&mut self, length: usize) {
         self.length = length as f32;
 
-        let length = self.length * self.attack;
-        self.delta = 1.0 / length;
-        self.clock.reset(length as usize);
+        let gate = match self.timing {
+            Timing::Relative => self.length * (1.0 - self.release),
+            Timing::Absolute => self.length,
+        };
+        self.gate.reset(gate as usize);
+    }
+
+    pub fn stop(&mut self) {
+        if self.gated() {
+            self.enter(State::Release);
+        }
     }
 
     pub fn next(&mut self) -> f32 {
+        self.value = self.value * self.coefficient + self.delta;
+
         if self.clock.tick() {
-            self.state = self.state.next();
-
-            match self.state {
-                State::Decay => {
-                    let length = self.length * self.decay;
-                    self.delta = (self.sustain - self.value) / length;
-                    self.clock.reset(length as usize);
-                }
-                State::Sustain => {
-                    self.delta = 0.0;
-                    self.clock.reset(
-                        (self.length * (1.0 - self.attack - self.decay - self.release)) as usize,
-                    );
-                }
-                State::Release => {
-                    let length = self.length * self.release;
-                    self.delta = (-self.value) / length;
-                    self.clock.reset(length as usize);
-                }
-                _ => {
-                    self.state = State::Off;
-                    self.delta = 0.0;
-                    self.value = 0.0;
-                }
-            }
+            self.enter(self.state.next());
+        }
+        if self.gate.tick() && self.gated() {
+            self.enter(State::Release);
         }
 
-        self.value += self.delta;
         self.value
     }
+
+    pub fn active(&self) -> bool {
+        self.state != State::Off
+    }
+
+    pub fn gated(&self) -> bool {
+        matches!(self.state, State::Attack | State::Decay | State::Sustain)
+    }
+
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    pub fn stages(&self) -> [f32; 3] {
+        [self.attack, self.decay, self.release]
+    }
+
+    pub fn snapshot(&self) -> EnvelopeState {
+        EnvelopeState {
+            attack: self.attack,
+            decay: self.decay,
+            sustain: self.sustain,
+            release: self.release,
+            curve: self.curve,
+            timing: self.timing as u8,
+        }
+    }
+
+    fn enter(&mut self, state: State) {
+        self.state = state;
+
+        match self.state {
+            State::Attack => self.segment(1.0, self.attack),
+            State::Decay => self.segment(self.sustain, self.decay),
+            State::Release => self.segment(0.0, self.release),
+            State::Sustain => {
+                self.coefficient = 1.0;
+                self.delta = 0.0;
+                self.clock.reset(usize::MAX);
+            }
+            State::Off => {
+                self.value = 0.0;
+                self.coefficient = 1.0;
+                self.delta = 0.0;
+                self.clock.reset(usize::MAX);
+            }
+        }
+    }
+
+    fn segment(&mut self, target: f32, stage: f32) {
+        let length = match self.timing {
+            Timing::Relative => self.length * stage,
+            Timing::Absolute => self.sample_rate * stage,
+        } as usize;
+
+        if length == 0 {
+            self.value = target;
+            return self.enter(self.state.next());
+        }
+
+        self.clock.reset(length);
+        let length = length as f32;
+
+        if self.curve.abs() < f32::EPSILON {
+            self.coefficient = 1.0;
+            self.delta = (target - self.value) / length;
+        } else {
+            let tail = expf(-self.curve);
+            let overshoot = target - (self.value - target) * tail / (1.0 - tail);
+            self.coefficient = expf(-self.curve / length);
+            self.delta = overshoot * (1.0 - self.coefficient);
+        }
+    }
 }
 
 impl Default for ADSR {
     fn default() -> Self {
         ADSR {
+            sample_rate: 0.0,
             attack: 0.0,
             decay: 0.0,
             sustain: 0.0,
             release: 0.0,
+            curve: 0.0,
+            timing: Timing::Relative,
             value: 0.0,
             length: 0.0,
+            coefficient: 1.0,
             delta: 0.0,
-            clock: Clock::default(),
+            clock: Clock::deadline(usize::MAX),
+            gate: Clock::deadline(usize::MAX),
             state: State::Off,
         }
     }
@@ -98,13 +243,13 @@ impl fmt::Display for ADSR {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "({:.2}, {:.2}, {:.2}, {:.2})",
-            self.attack, self.decay, self.sustain, self.release
+            "({:.2}, {:.2}, {:.2}, {:.2}, {:.2}, {})",
+            self.attack, self.decay, self.sustain, self.release, self.curve, self.timing
         )
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 enum State {
     Attack,
     Decay,
@@ -116,7 +261,6 @@ enum State {
 impl State {
     fn next(&self) -> State {
         match self {
-            State::Off => State::Attack,
             State::Attack => State::Decay,
             State::Decay => State::Sustain,
             State::Sustain => State::Release,
@@ -131,82 +275,158 @@ mod tests {
 
     use super::*;
 
+    fn values(adsr: &mut ADSR, count: usize) -> Vec<f32> {
+        (0..count).map(|_| adsr.next()).collect()
+    }
+
     #[test]
-    fn new() {
+    fn random() {
         let mut rng = SmallRng::seed_from_u64(0);
 
-        let adsr = ADSR::new(&mut rng);
+        let adsr = ADSR::random(&mut rng, 44100.0, &PolyConfig::default());
         assert_eq!(0.2739218, adsr.attack);
         assert_eq!(0.049522623, adsr.decay);
         assert_eq!(0.9389341, adsr.sustain);
         assert_eq!(0.028486688, adsr.release);
+        assert!(CURVE.contains(&adsr.curve));
+        assert_eq!(Timing::Relative, adsr.timing);
         assert_eq!(0.0, adsr.value);
         assert_eq!(0.0, adsr.length);
-        assert_eq!(0.0, adsr.delta);
-        assert_eq!(true, adsr.clock.ready());
-        assert_eq!(State::Off, adsr.state);
+        assert!(!adsr.active());
     }
 
     #[test]
-    fn attack() {
-        let mut adsr = ADSR {
-            attack: 0.5,
-            decay: 0.5,
-            ..Default::default()
-        };
+    fn set() {
+        let mut adsr = ADSR::new(8.0, 0.5, 0.5, 0.0, 0.0);
         adsr.start(8);
-
+        adsr.next();
+        adsr.set(0.1, 0.2, 0.3, 0.4);
+        adsr.set_curve(2.0, Timing::Absolute);
         assert_eq!(
-            (0..3).map(|_| adsr.next()).collect::<Vec<f32>>(),
-            vec![0.25, 0.5, 0.75]
-        )
+            "(0.10, 0.20, 0.30, 0.40, 2.00, Absolute)",
+            format!("{}", adsr)
+        );
+        assert_eq!(Timing::Absolute, adsr.timing());
+        assert_eq!([0.1, 0.2, 0.4], adsr.stages());
+        assert_eq!(0.5, adsr.next());
+        assert_eq!(State::Attack, adsr.state);
+    }
+
+    #[test]
+    fn attack() {
+        let mut adsr = ADSR::new(8.0, 0.5, 0.5, 0.0, 0.0);
+        adsr.start(8);
+        assert_eq!(vec![0.25, 0.5, 0.75, 1.0], values(&mut adsr, 4));
+        assert_eq!(State::Decay, adsr.state);
     }
 
     #[test]
     fn decay() {
-        let mut adsr = ADSR {
-            decay: 0.5,
-            sustain: 0.5,
-            value: 1.0,
-            length: 8.0,
-            state: State::Attack,
-            ..Default::default()
-        };
+        let mut adsr = ADSR::new(8.0, 0.25, 0.5, 0.5, 0.0);
+        adsr.start(8);
         assert_eq!(
-            (0..4).map(|_| adsr.next()).collect::<Vec<f32>>(),
-            vec![0.875, 0.75, 0.625, 0.5]
-        )
+            vec![0.5, 1.0, 0.875, 0.75, 0.625, 0.5, 0.5, 0.0],
+            values(&mut adsr, 8)
+        );
+        assert!(!adsr.active());
     }
 
     #[test]
-    fn sustain() {
-        let mut adsr = ADSR {
-            attack: 0.2,
-            decay: 0.2,
-            release: 0.2,
-            value: 0.5,
-            length: 10.0,
-            state: State::Decay,
-            ..Default::default()
-        };
+    fn release() {
+        let mut adsr = ADSR::new(8.0, 0.25, 0.25, 0.5, 0.25);
+        adsr.start(8);
         assert_eq!(
-            (0..4).map(|_| adsr.next()).collect::<Vec<f32>>(),
-            vec![0.5, 0.5, 0.5, 0.5]
-        )
+            vec![0.5, 1.0, 0.75, 0.5, 0.5, 0.5, 0.25, 0.0],
+            values(&mut adsr, 8)
+        );
+        assert!(!adsr.active());
     }
 
     #[test]
-    fn release() {
-        let mut adsr = ADSR {
-            release: 0.5,
-            value: 0.5,
-            length: 8.0,
-            state: State::Sustain,
-            ..Default::default()
-        };
+    fn curve() {
+        let mut adsr = ADSR::new(16.0, 0.5, 0.0, 1.0, 0.0);
+        adsr.set_curve(4.0, Timing::Relative);
+        adsr.start(32);
+        let values = values(&mut adsr, 16);
+
+        assert!(values.windows(2).all(|w| w[1] > w[0]));
+        assert!(values[..15]
+            .iter()
+            .enumerate()
+            .all(|(index, &value)| value > (index + 1) as f32 / 16.0));
+        assert!((values[15] - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn absolute() {
+        let mut adsr = ADSR::new(8.0, 0.5, 0.0, 1.0, 0.5);
+        adsr.set_curve(0.0, Timing::Absolute);
+        adsr.start(2);
         assert_eq!(
-            (0..4).map(|_| adsr.next()).collect::<Vec<f32>>(),
-            vec![0.375, 0.25, 0.125, 0.0]
-        )
+            vec![0.25, 0.5, 0.375, 0.25, 0.125, 0.0],
+            values(&mut adsr, 6)
+        );
+        assert!(!adsr.active());
+
+        adsr.start(32);
+        let values = values(&mut adsr, 36);
+        assert!(values[3..32].iter().all(|&value| value == 1.0));
+        assert_eq!(vec![0.75, 0.5, 0.25, 0.0], values[32..].to_vec());
+    }
+
+    #[test]
+    fn retrigger() {
+        let mut adsr = ADSR::new(64.0, 0.25, 0.25, 0.5, 0.25);
+        adsr.set_curve(2.0, Timing::Relative);
+        adsr.start(64);
+        let mut values = values(&mut adsr, 56);
+        assert_eq!(State::Release, adsr.state);
+
+        let before = adsr.value();
+        adsr.start(64);
+        values.extend((0..64).map(|_| adsr.next()));
+
+        assert!(before > 0.1);
+        assert!(values[56] > before);
+        assert!(values.windows(2).all(|w| (w[1] - w[0]).abs() < 0.15));
+    }
+
+    #[test]
+    fn legato() {
+        let mut adsr = ADSR::new(8.0, 0.25, 0.25, 0.5, 0.25);
+        adsr.start(8);
+        values(&mut adsr, 5);
+        assert!(adsr.gated());
+
+        adsr.extend(16);
+        let values = values(&mut adsr, 16);
+        assert!(values[..12].iter().all(|&value| value == 0.5));
+        assert_eq!(vec![0.375, 0.25, 0.125, 0.0], values[12..].to_vec());
+        assert!(!adsr.active());
+    }
+
+    #[test]
+    fn stop() {
+        let mut adsr = ADSR::new(8.0, 0.25, 0.25, 0.5, 0.25);
+        adsr.start(8);
+        values(&mut adsr, 3);
+
+        adsr.stop();
+        assert_eq!(vec![0.375, 0.0, 0.0], values(&mut adsr, 3));
+        assert!(!adsr.active());
+
+        adsr.stop();
+        assert!(!adsr.active());
+    }
+
+    #[test]
+    fn restore() {
+        let mut adsr = ADSR::new(44100.0, 0.1, 0.2, 0.3, 0.4);
+        adsr.set_curve(1.5, Timing::Absolute);
+        let state = adsr.snapshot();
+        assert_eq!(state, ADSR::restore(44100.0, &state).unwrap().snapshot());
+
+        let invalid = EnvelopeState { timing: 2, ..state };
+        assert!(ADSR::restore(44100.0, &invalid).is_err());
     }
 }
@@ -0,0 +1,79 @@
+use core::fmt;
+
+use rand::{rngs::SmallRng, Rng};
+
+use crate::constants::INDEX;
+
+pub struct Algorithm {
+    pub name: &'static str,
+    pub modulators: [[bool; 3]; 3],
+    pub carriers: [bool; 3],
+    pub feedback: usize,
+}
+
+impl Algorithm {
+    pub fn indices(&self, rng: &mut SmallRng) -> [[f32; 3]; 3] {
+        let mut indices = [[0.0; 3]; 3];
+        for (carrier, modulators) in self.modulators.iter().enumerate() {
+            for (modulator, &routed) in modulators.iter().enumerate() {
+                if routed && modulator > carrier {
+                    indices[carrier][modulator] = rng.gen_range(INDEX);
+                }
+            }
+        }
+        indices
+    }
+
+    pub fn gain(&self) -> f32 {
+        1.0 / self
+            .carriers
+            .iter()
+            .filter(|&&carrier| carrier)
+            .count()
+            .max(1) as f32
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::constants::ALGORITHMS;
+
+    #[test]
+    fn indices() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let stack = &ALGORITHMS[0];
+        let indices = stack.indices(&mut rng);
+        assert_eq!(0.0, indices[0][2]);
+        assert_eq!(0.0, indices[1][0]);
+        assert!(INDEX.contains(&indices[0][1]));
+        assert!(INDEX.contains(&indices[1][2]));
+    }
+
+    #[test]
+    fn routing() {
+        for algorithm in &ALGORITHMS {
+            assert!(algorithm.carriers.iter().any(|&carrier| carrier));
+            for (carrier, modulators) in algorithm.modulators.iter().enumerate() {
+                for (modulator, &routed) in modulators.iter().enumerate() {
+                    assert!(!routed || modulator > carrier, "{}", algorithm);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gain() {
+        assert_eq!(1.0, ALGORITHMS[0].gain());
+        assert_eq!(1.0 / 3.0, ALGORITHMS[4].gain());
+    }
+}
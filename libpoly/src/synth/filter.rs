@@ -1,52 +1,264 @@
-use core::{f32::consts::FRAC_PI_2, fmt};
+use core::f32::consts::{FRAC_PI_2, PI};
+use core::fmt;
 
-use libm::{sinf, tanhf};
-use rand::{rngs::SmallRng, Rng};
+use libm::{exp2f, log2f, sinf, tanf, tanhf};
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng};
 
-use crate::constants::{CUTOFF, Q};
+use super::adsr::ADSR;
+use super::smooth::Smooth;
+use crate::config::PolyConfig;
+use crate::constants::{
+    CEILING, CONTROL, DAMPING, FILTER_DEPTH, FLOOR, KEY, SENSITIVITY, TRACKING,
+};
+use crate::error::Error;
+use crate::snapshot::FilterState;
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Response {
+    Ladder,
+    Lowpass,
+    Highpass,
+    Bandpass,
+    Notch,
+}
+
+impl TryFrom<u8> for Response {
+    type Error = Error;
+
+    fn try_from(response: u8) -> Result<Self, Error> {
+        match response {
+            0 => Ok(Response::Ladder),
+            1 => Ok(Response::Lowpass),
+            2 => Ok(Response::Highpass),
+            3 => Ok(Response::Bandpass),
+            4 => Ok(Response::Notch),
+            _ => Err(Error::Snapshot),
+        }
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Response::Ladder => "Ladder",
+                Response::Lowpass => "Lowpass",
+                Response::Highpass => "Highpass",
+                Response::Bandpass => "Bandpass",
+                Response::Notch => "Notch",
+            }
+        )
+    }
+}
+
+#[derive(Clone)]
 pub struct Filter {
-    cutoff: f32,
-    q: f32,
-    resonance: f32,
-    p: f32,
-    k: f32,
+    sample_rate: f32,
+    response: Response,
+    cutoff: Smooth,
+    q: Smooth,
+    envelope: ADSR,
+    depth: f32,
+    tracking: f32,
+    sensitivity: f32,
+    offset: f32,
+    shift: [f32; 2],
+    shifted: bool,
+    counter: usize,
+    coefficients: [f32; 4],
+    targets: [f32; 4],
+    deltas: [f32; 4],
     px: f32,
     s: [f32; 4],
     ps: [f32; 4],
+    ic: [f32; 2],
 }
 
 impl Filter {
-    pub fn new(rng: &mut SmallRng, sample_rate: f32) -> Self {
-        let cutoff = rng.gen_range(CUTOFF);
-        let q = rng.gen_range(Q);
+    pub fn new(sample_rate: f32, response: Response, cutoff: f32, q: f32) -> Self {
+        let mut filter = Filter {
+            sample_rate,
+            response,
+            cutoff: Smooth::new(cutoff),
+            q: Smooth::new(q),
+            envelope: ADSR::default(),
+            depth: 0.0,
+            tracking: 0.0,
+            sensitivity: 0.0,
+            offset: 0.0,
+            shift: [0.0; 2],
+            shifted: false,
+            counter: 0,
+            coefficients: [0.0; 4],
+            targets: [0.0; 4],
+            deltas: [0.0; 4],
+            px: 0.0,
+            s: [0.0; 4],
+            ps: [0.0; 4],
+            ic: [0.0; 2],
+        };
+        filter.snap(cutoff, q);
+        filter
+    }
+
+    pub fn random(
+        rng: &mut SmallRng,
+        sample_rate: f32,
+        config: &PolyConfig,
+    ) -> Result<Self, Error> {
+        let cutoff = rng.gen_range(config.cutoff.clone());
+        let q = rng.gen_range(config.q.clone());
+        let response = *config.responses.choose(rng).ok_or(Error::Rng)?;
+
+        let envelope = ADSR::random(rng, sample_rate, config);
+        let depth = rng.gen_range(FILTER_DEPTH);
+        let tracking = rng.gen_range(TRACKING);
+        let sensitivity = rng.gen_range(SENSITIVITY);
+
+        let mut filter = Self::new(sample_rate, response, cutoff, q);
+        filter.set_modulation(envelope, depth, tracking, sensitivity);
+        Ok(filter)
+    }
+
+    pub fn set(&mut self, cutoff: f32, q: f32) {
+        self.cutoff.set(cutoff);
+        self.q.set(q);
+    }
+
+    pub fn set_modulation(&mut self, envelope: ADSR, depth: f32, tracking: f32, sensitivity: f32) {
+        self.envelope = envelope;
+        self.depth = depth;
+        self.tracking = tracking;
+        self.sensitivity = sensitivity;
+    }
+
+    pub fn set_response(&mut self, response: Response) {
+        self.response = response;
+        self.px = 0.0;
+        self.s = [0.0; 4];
+        self.ps = [0.0; 4];
+        self.ic = [0.0; 2];
+        self.snap(self.cutoff.value(), self.q.value());
+    }
+
+    pub fn set_shift(&mut self, cutoff: f32, q: f32) {
+        if self.shift != [cutoff, q] {
+            self.shift = [cutoff, q];
+            self.shifted = true;
+        }
+    }
 
-        let c = 2.0 * cutoff / sample_rate;
+    pub fn start(&mut self, length: usize, frequency: f32, velocity: f32) {
+        self.envelope.start(length);
+        self.offset = self.tracking * log2f(frequency / KEY) + self.sensitivity * (velocity - 1.0);
+    }
+
+    pub fn extend(&mut self, length: usize) {
+        self.envelope.extend(length);
+    }
+
+    pub fn stop(&mut self) {
+        self.envelope.stop();
+    }
+
+    pub fn settle(&mut self) {
+        self.cutoff.settle();
+        self.q.settle();
+        self.snap(self.cutoff.target(), self.q.target());
+    }
+
+    fn snap(&mut self, cutoff: f32, q: f32) {
+        let (cutoff, q) = self.modulate(cutoff, q, self.envelope.value());
+        self.targets = self.coefficients(cutoff, q);
+        self.coefficients = self.targets;
+        self.deltas = [0.0; 4];
+        self.counter = 0;
+    }
+
+    pub fn snapshot(&self) -> FilterState {
+        FilterState {
+            response: self.response as u8,
+            cutoff: self.cutoff.target(),
+            q: self.q.target(),
+            envelope: self.envelope.snapshot(),
+            depth: self.depth,
+            tracking: self.tracking,
+            sensitivity: self.sensitivity,
+        }
+    }
+
+    fn modulate(&self, cutoff: f32, q: f32, envelope: f32) -> (f32, f32) {
+        let cutoff = cutoff * exp2f(self.depth * envelope + self.offset + self.shift[0]);
+        (
+            cutoff.clamp(FLOOR, self.sample_rate * CEILING),
+            (q + self.shift[1]).clamp(0.0, 1.0),
+        )
+    }
+
+    fn coefficients(&self, cutoff: f32, q: f32) -> [f32; 4] {
+        if self.response != Response::Ladder {
+            let g = tanf(PI * cutoff / self.sample_rate);
+            let damping = 2.0 - q * (2.0 - DAMPING);
+            let a = 1.0 / (1.0 + g * (g + damping));
+            return [damping, a, g * a, g * g * a];
+        }
+
+        let c = 2.0 * cutoff / self.sample_rate;
         let p = c * (1.8 - 0.8 * c);
         let k = 2.0 * sinf(c * FRAC_PI_2) - 1.0;
         let t1 = (1.0 - p) * 1.386249;
         let t2 = 12.0 + t1 * t1;
         let resonance = q * (t2 + 6.0 * t1) / (t2 - 6.0 * t1);
+        [p, k, resonance, 0.0]
+    }
 
-        Filter {
-            cutoff,
-            q,
-            resonance,
-            p,
-            k,
-            px: 0.0,
-            s: [0.0; 4],
-            ps: [0.0; 4],
+    fn update(&mut self, envelope: f32) {
+        self.counter = CONTROL;
+        if self.cutoff.active() || self.q.active() || self.envelope.active() || self.shifted {
+            self.shifted = false;
+            let (cutoff, q) = (self.cutoff.next(), self.q.next());
+            let (cutoff, q) = self.modulate(cutoff, q, envelope);
+            self.targets = self.coefficients(cutoff, q);
+        }
+        for ((delta, target), coefficient) in self
+            .deltas
+            .iter_mut()
+            .zip(&self.targets)
+            .zip(&self.coefficients)
+        {
+            *delta = (target - coefficient) / CONTROL as f32;
+        }
+    }
+
+    fn step(&mut self) {
+        self.counter -= 1;
+        if self.counter == 0 {
+            self.coefficients = self.targets;
+        } else {
+            for (coefficient, delta) in self.coefficients.iter_mut().zip(&self.deltas) {
+                *coefficient += delta;
+            }
         }
     }
 
     pub fn generate(&mut self, input: f32) -> f32 {
-        let x = -self.resonance * self.s[3] + input;
+        if self.response == Response::Ladder {
+            self.ladder(input)
+        } else {
+            self.svf(input)
+        }
+    }
 
-        self.s[0] = (x + self.px) * self.p - self.k * self.s[0];
-        self.s[1] = (self.s[0] + self.ps[0]) * self.p - self.k * self.s[1];
-        self.s[2] = (self.s[1] + self.ps[1]) * self.p - self.k * self.s[2];
-        self.s[3] = tanhf((self.s[2] + self.ps[2]) * self.p - self.k * self.s[3]);
+    fn ladder(&mut self, input: f32) -> f32 {
+        let [p, k, resonance, _] = self.coefficients;
+        let x = -resonance * self.s[3] + input;
+
+        self.s[0] = (x + self.px) * p - k * self.s[0];
+        self.s[1] = (self.s[0] + self.ps[0]) * p - k * self.s[1];
+        self.s[2] = (self.s[1] + self.ps[1]) * p - k * self.s[2];
+        self.s[3] = tanhf((self.s[2] + self.ps[2]) * p - k * self.s[3]);
 
         self.px = x;
         self.ps[0] = self.s[0];
@@ -55,10 +267,263 @@ impl Filter {
 
         self.s[3]
     }
+
+    fn svf(&mut self, input: f32) -> f32 {
+        let [damping, a1, a2, a3] = self.coefficients;
+        let v3 = input - self.ic[1];
+        let v1 = a1 * self.ic[0] + a2 * v3;
+        let v2 = self.ic[1] + a2 * self.ic[0] + a3 * v3;
+        self.ic[0] = 2.0 * v1 - self.ic[0];
+        self.ic[1] = 2.0 * v2 - self.ic[1];
+
+        let high = input - damping * v1 - v2;
+        match self.response {
+            Response::Lowpass => v2,
+            Response::Highpass => high,
+            Response::Bandpass => damping * v1,
+            _ => v2 + high,
+        }
+    }
+
+    pub fn process(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            let envelope = self.envelope.next();
+            if self.counter == 0 {
+                self.update(envelope);
+            }
+            self.step();
+
+            *sample = self.generate(*sample);
+        }
+    }
 }
 
 impl fmt::Display for Filter {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "({:.2}Hz, {:.2})", self.cutoff, self.q)
+        write!(
+            f,
+            "({}, {:.2}Hz, {:.2}) [MOD] {} ({:.2}, {:.2}, {:.2})",
+            self.response,
+            self.cutoff.target(),
+            self.q.target(),
+            self.envelope,
+            self.depth,
+            self.tracking,
+            self.sensitivity
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::SMOOTH;
+
+    use super::*;
+
+    fn magnitude(filter: &mut Filter, frequency: f64) -> f32 {
+        let mut out: Vec<f32> = (0..88_200)
+            .map(|n| (2.0 * core::f64::consts::PI * frequency * n as f64 / 44100.0).sin() as f32)
+            .collect();
+        filter.process(&mut out);
+        let power = out[44_100..].iter().map(|x| x * x).sum::<f32>() / 44_100.0;
+        (2.0 * power).sqrt()
+    }
+
+    fn analytic(response: Response, damping: f32, frequency: f32) -> f32 {
+        let w = tanf(PI * frequency / 44100.0) / tanf(PI * 1000.0 / 44100.0);
+        let denominator = ((1.0 - w * w).powi(2) + (damping * w).powi(2)).sqrt();
+        match response {
+            Response::Lowpass => 1.0 / denominator,
+            Response::Highpass => w * w / denominator,
+            Response::Bandpass => damping * w / denominator,
+            Response::Notch => (1.0 - w * w).abs() / denominator,
+            Response::Ladder => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn new() {
+        let filter = Filter::new(44100.0, Response::Ladder, 1000.0, 0.5);
+        assert_eq!(0.07998724, filter.coefficients[0]);
+        assert_eq!(-0.8576446, filter.coefficients[1]);
+        assert_eq!(1.7808404, filter.coefficients[2]);
+    }
+
+    #[test]
+    fn set() {
+        let mut filter = Filter::new(44100.0, Response::Ladder, 1000.0, 0.5);
+        let target = Filter::new(44100.0, Response::Ladder, 2000.0, 0.25);
+        filter.set(2000.0, 0.25);
+        assert_eq!(
+            "(Ladder, 2000.00Hz, 0.25) [MOD] (0.00, 0.00, 0.00, 0.00, 0.00, Relative) (0.00, 0.00, 0.00)",
+            format!("{}", filter)
+        );
+
+        let mut p = Vec::new();
+        for _ in 0..SMOOTH + 1 {
+            filter.process(&mut [0.0; CONTROL]);
+            p.push(filter.coefficients[0]);
+        }
+        assert!(p.windows(2).all(|w| w[0] <= w[1]));
+        assert!(p[0] < p[SMOOTH / 2] && p[SMOOTH / 2] < target.coefficients[0]);
+        assert_eq!(target.coefficients[0], p[SMOOTH - 1]);
+        assert_eq!(target.coefficients[2], filter.coefficients[2]);
+    }
+
+    #[test]
+    fn settle() {
+        let mut filter = Filter::new(44100.0, Response::Ladder, 1000.0, 0.5);
+        let target = Filter::new(44100.0, Response::Ladder, 500.0, 0.5);
+        filter.set(500.0, 0.5);
+        filter.settle();
+        assert_eq!(target.coefficients[0], filter.coefficients[0]);
+        assert_eq!(target.coefficients[1], filter.coefficients[1]);
+    }
+
+    #[test]
+    fn svf() {
+        let responses = [
+            Response::Lowpass,
+            Response::Highpass,
+            Response::Bandpass,
+            Response::Notch,
+        ];
+        for response in responses {
+            for q in [0.0, 0.5, 0.9] {
+                for frequency in [100.0, 500.0, 1000.0, 2000.0, 8000.0] {
+                    let mut filter = Filter::new(44100.0, response, 1000.0, q);
+                    let expected = analytic(response, filter.coefficients[0], frequency);
+                    let actual = magnitude(&mut filter, frequency as f64);
+                    assert!(
+                        (expected - actual).abs() < 1e-3,
+                        "{} {} {}Hz: {} != {}",
+                        response,
+                        q,
+                        frequency,
+                        expected,
+                        actual
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ladder() {
+        let mut low = Filter::new(44100.0, Response::Ladder, 1000.0, 0.0);
+        let mut high = Filter::new(44100.0, Response::Ladder, 1000.0, 0.0);
+        assert!(magnitude(&mut low, 100.0) > 0.7);
+        assert!(magnitude(&mut high, 8000.0) < 1e-3);
+    }
+
+    #[test]
+    fn set_response() {
+        let mut filter = Filter::new(44100.0, Response::Ladder, 1000.0, 0.5);
+        filter.set_response(Response::Notch);
+        let target = Filter::new(44100.0, Response::Notch, 1000.0, 0.5);
+        assert_eq!(target.coefficients, filter.coefficients);
+        assert_eq!(
+            "(Notch, 1000.00Hz, 0.50) [MOD] (0.00, 0.00, 0.00, 0.00, 0.00, Relative) (0.00, 0.00, 0.00)",
+            format!("{}", filter)
+        );
+        assert!(magnitude(&mut filter, 1000.0) < 1e-3);
+
+        assert!(matches!(Response::try_from(4), Ok(Response::Notch)));
+        assert!(Response::try_from(5).is_err());
+    }
+
+    #[test]
+    fn envelope() {
+        let mut filter = Filter::new(44100.0, Response::Lowpass, 500.0, 0.5);
+        filter.set_modulation(ADSR::new(44100.0, 0.5, 0.1, 0.5, 0.1), 2.0, 0.0, 0.0);
+        filter.start(1000, KEY, 1.0);
+        filter.settle();
+        let base = Filter::new(44100.0, Response::Lowpass, 500.0, 0.5);
+        assert_eq!(base.coefficients, filter.coefficients);
+
+        let mut g = Vec::new();
+        for _ in 0..1000 / CONTROL {
+            filter.process(&mut [0.0; CONTROL]);
+            g.push(filter.coefficients[2] / filter.coefficients[1]);
+        }
+        let peak = Filter::new(44100.0, Response::Lowpass, 2000.0, 0.5);
+        let peak = peak.coefficients[2] / peak.coefficients[1];
+        assert!(g[..15].windows(2).all(|w| w[0] < w[1]));
+        assert!((g[15] - peak).abs() < 0.01);
+        assert!(g[20] < g[15]);
+    }
+
+    #[test]
+    fn tracking() {
+        let mut filter = Filter::new(44100.0, Response::Ladder, 500.0, 0.5);
+        filter.set_modulation(ADSR::default(), 0.0, 1.0, 1.0);
+
+        filter.start(1000, KEY * 2.0, 1.0);
+        filter.settle();
+        let octave = Filter::new(44100.0, Response::Ladder, 1000.0, 0.5);
+        assert!((octave.coefficients[0] - filter.coefficients[0]).abs() < 1e-6);
+
+        filter.start(1000, KEY, 0.0);
+        filter.settle();
+        let soft = Filter::new(44100.0, Response::Ladder, 250.0, 0.5);
+        assert!((soft.coefficients[0] - filter.coefficients[0]).abs() < 1e-6);
+
+        filter.start(1000, KEY * 1024.0, 1.0);
+        filter.settle();
+        let ceiling = Filter::new(44100.0, Response::Ladder, 44100.0 * CEILING, 0.5);
+        assert_eq!(ceiling.coefficients, filter.coefficients);
+    }
+
+    #[test]
+    fn retrigger() {
+        let mut filter = Filter::new(44100.0, Response::Ladder, 500.0, 0.5);
+        filter.set_modulation(ADSR::default(), 0.0, 1.0, 0.0);
+        filter.start(1000, KEY, 1.0);
+        filter.settle();
+        filter.process(&mut [0.0; CONTROL]);
+
+        let base = Filter::new(44100.0, Response::Ladder, 500.0, 0.5);
+        let octave = Filter::new(44100.0, Response::Ladder, 1000.0, 0.5);
+        filter.start(1000, KEY * 2.0, 1.0);
+        assert_eq!(base.coefficients, filter.coefficients);
+
+        filter.process(&mut [0.0; CONTROL / 2]);
+        assert!(filter.coefficients[0] > base.coefficients[0]);
+        assert!(filter.coefficients[0] < octave.coefficients[0]);
+
+        filter.process(&mut [0.0; CONTROL / 2]);
+        assert!((octave.coefficients[0] - filter.coefficients[0]).abs() < 1e-6);
+    }
+
+    #[test]
+    fn shift() {
+        let mut filter = Filter::new(44100.0, Response::Bandpass, 500.0, 0.2);
+        filter.set_shift(1.0, 0.5);
+        filter.process(&mut [0.0; CONTROL]);
+        let target = Filter::new(44100.0, Response::Bandpass, 1000.0, 0.7);
+        assert!((target.coefficients[1] - filter.coefficients[1]).abs() < 1e-6);
+        assert!((target.coefficients[0] - filter.coefficients[0]).abs() < 1e-6);
+
+        filter.set_shift(0.0, 2.0);
+        filter.process(&mut [0.0; CONTROL]);
+        let target = Filter::new(44100.0, Response::Bandpass, 500.0, 1.0);
+        assert_eq!(target.coefficients, filter.coefficients);
+    }
+
+    #[test]
+    fn ramp() {
+        let mut filter = Filter::new(44100.0, Response::Ladder, 1000.0, 0.5);
+        let target = Filter::new(44100.0, Response::Ladder, 2000.0, 0.5);
+        filter.set(2000.0, 0.5);
+
+        let mut p = Vec::new();
+        for _ in 0..CONTROL * 2 {
+            filter.process(&mut [0.0]);
+            p.push(filter.coefficients[0]);
+        }
+        assert!(p.windows(2).all(|w| w[0] < w[1]));
+        let steps: Vec<f32> = p[..CONTROL - 1].windows(2).map(|w| w[1] - w[0]).collect();
+        assert!(steps.iter().all(|step| (step - steps[0]).abs() < 1e-6));
+        assert!(p[CONTROL * 2 - 1] < target.coefficients[0]);
     }
 }
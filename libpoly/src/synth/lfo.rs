@@ -0,0 +1,261 @@
+use core::f32::consts::TAU;
+use core::fmt;
+
+use libm::{floorf, sinf};
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::constants::{LFO_RATE, LFO_SYNC, LFO_SYNCED, PPQ, WALK};
+use crate::error::Error;
+use crate::snapshot::LfoState;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Shape {
+    Sine,
+    Triangle,
+    Hold,
+    Walk,
+}
+
+impl TryFrom<u8> for Shape {
+    type Error = Error;
+
+    fn try_from(shape: u8) -> Result<Self, Error> {
+        match shape {
+            0 => Ok(Shape::Sine),
+            1 => Ok(Shape::Triangle),
+            2 => Ok(Shape::Hold),
+            3 => Ok(Shape::Walk),
+            _ => Err(Error::Snapshot),
+        }
+    }
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Shape::Sine => "Sine",
+                Shape::Triangle => "Triangle",
+                Shape::Hold => "Hold",
+                Shape::Walk => "Walk",
+            }
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct Lfo {
+    sample_rate: f32,
+    shape: Shape,
+    rate: f32,
+    sync: usize,
+    rng: SmallRng,
+    phase: f32,
+    from: f32,
+    to: f32,
+}
+
+impl Lfo {
+    pub fn new(sample_rate: f32, shape: Shape, rate: f32, sync: usize, seed: u64) -> Self {
+        Lfo {
+            sample_rate,
+            shape,
+            rate,
+            sync,
+            rng: SmallRng::seed_from_u64(seed),
+            phase: 0.0,
+            from: 0.0,
+            to: 0.0,
+        }
+    }
+
+    pub fn random(rng: &mut SmallRng, sample_rate: f32) -> Result<Self, Error> {
+        let shape = *[Shape::Sine, Shape::Triangle, Shape::Hold, Shape::Walk]
+            .choose(rng)
+            .ok_or(Error::Rng)?;
+        let rate = rng.gen_range(LFO_RATE);
+        let sync = if rng.gen_bool(LFO_SYNCED) {
+            *LFO_SYNC.choose(rng).ok_or(Error::Rng)?
+        } else {
+            0
+        };
+
+        Ok(Self::new(sample_rate, shape, rate, sync, rng.gen()))
+    }
+
+    pub fn snapshot(&mut self) -> LfoState {
+        let seed = self.rng.gen();
+        self.rng = SmallRng::seed_from_u64(seed);
+
+        LfoState {
+            shape: self.shape as u8,
+            rate: self.rate,
+            sync: self.sync,
+            seed,
+            phase: self.phase,
+            from: self.from,
+            to: self.to,
+        }
+    }
+
+    pub fn restore(sample_rate: f32, state: &LfoState) -> Result<Self, Error> {
+        let shape = Shape::try_from(state.shape)?;
+        if !(0.0..1.0).contains(&state.phase)
+            || !(-1.0..=1.0).contains(&state.from)
+            || !(-1.0..=1.0).contains(&state.to)
+        {
+            return Err(Error::Snapshot);
+        }
+
+        let mut lfo = Self::new(sample_rate, shape, state.rate, state.sync, state.seed);
+        lfo.phase = state.phase;
+        lfo.from = state.from;
+        lfo.to = state.to;
+        Ok(lfo)
+    }
+
+    pub fn next(&mut self, frames: usize, tempo: f32) -> f32 {
+        let period = if self.sync > 0 {
+            self.sync as f32 * tempo / PPQ as f32
+        } else {
+            self.sample_rate / self.rate
+        };
+
+        self.phase += frames as f32 / period;
+        if self.phase >= 1.0 {
+            self.phase -= floorf(self.phase);
+            self.cycle();
+        }
+        self.value()
+    }
+
+    fn cycle(&mut self) {
+        match self.shape {
+            Shape::Hold => self.to = self.rng.gen_range(-1.0..=1.0),
+            Shape::Walk => {
+                self.from = self.to;
+                self.to = (self.to + self.rng.gen_range(-WALK..=WALK)).clamp(-1.0, 1.0);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn value(&self) -> f32 {
+        match self.shape {
+            Shape::Sine => sinf(TAU * self.phase),
+            Shape::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+            Shape::Hold => self.to,
+            Shape::Walk => self.from + (self.to - self.from) * self.phase,
+        }
+    }
+}
+
+impl fmt::Display for Lfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.sync > 0 {
+            write!(f, "({}, {} ticks)", self.shape, self.sync)
+        } else {
+            write!(f, "({}, {:.2}Hz)", self.shape, self.rate)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(lfo: &mut Lfo, count: usize) -> Vec<f32> {
+        (0..count).map(|_| lfo.next(1, 100.0)).collect()
+    }
+
+    #[test]
+    fn random() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let lfo = Lfo::random(&mut rng, 44100.0).unwrap();
+            assert!(LFO_RATE.contains(&lfo.rate));
+            assert!(lfo.sync == 0 || LFO_SYNC.contains(&lfo.sync));
+        }
+    }
+
+    #[test]
+    fn sine() {
+        let mut lfo = Lfo::new(8.0, Shape::Sine, 1.0, 0, 0);
+        let values = values(&mut lfo, 8);
+        assert!((values[1] - 1.0).abs() < 1e-6);
+        assert!((values[5] + 1.0).abs() < 1e-6);
+        assert!(values[7].abs() < 1e-6);
+    }
+
+    #[test]
+    fn triangle() {
+        let mut lfo = Lfo::new(8.0, Shape::Triangle, 1.0, 0, 0);
+        assert_eq!(
+            vec![-0.5, 0.0, 0.5, 1.0, 0.5, 0.0, -0.5, -1.0],
+            values(&mut lfo, 8)
+        );
+    }
+
+    #[test]
+    fn hold() {
+        let mut lfo = Lfo::new(4.0, Shape::Hold, 1.0, 0, 0);
+        let values = values(&mut lfo, 12);
+        assert!(values[..3].iter().all(|&value| value == 0.0));
+        assert!(values[3..7].iter().all(|&value| value == values[3]));
+        assert!(values[7..11].iter().all(|&value| value == values[7]));
+        assert_ne!(values[3], values[7]);
+        assert!(values.iter().all(|value| (-1.0..=1.0).contains(value)));
+    }
+
+    #[test]
+    fn walk() {
+        let mut lfo = Lfo::new(4.0, Shape::Walk, 1.0, 0, 0);
+        let values = values(&mut lfo, 400);
+        assert!(values
+            .windows(2)
+            .all(|w| (w[1] - w[0]).abs() <= WALK / 4.0 + 1e-6));
+        assert!(values.iter().all(|value| (-1.0..=1.0).contains(value)));
+        assert!(values.iter().any(|&value| value != 0.0));
+    }
+
+    #[test]
+    fn wrap() {
+        let mut lfo = Lfo::new(4.0, Shape::Triangle, 1.0, 0, 0);
+        assert_eq!(0.0, lfo.next(9, 100.0));
+        assert_eq!(1.0, lfo.next(1, 100.0));
+    }
+
+    #[test]
+    fn restore() {
+        let mut lfo = Lfo::new(4.0, Shape::Walk, 1.0, 0, 0);
+        values(&mut lfo, 9);
+        let state = lfo.snapshot();
+        assert_eq!(0.25, state.phase);
+        assert_ne!(0.0, state.to);
+
+        let mut restored = Lfo::restore(4.0, &state).unwrap();
+        assert_eq!(values(&mut lfo, 20), values(&mut restored, 20));
+
+        let mut invalid = state;
+        invalid.phase = 1.0;
+        assert!(Lfo::restore(4.0, &invalid).is_err());
+
+        let mut invalid = state;
+        invalid.to = f32::NAN;
+        assert!(Lfo::restore(4.0, &invalid).is_err());
+
+        let mut invalid = state;
+        invalid.shape = 4;
+        assert!(Lfo::restore(4.0, &invalid).is_err());
+    }
+
+    #[test]
+    fn sync() {
+        let mut lfo = Lfo::new(44100.0, Shape::Triangle, 1.0, PPQ, 0);
+        assert_eq!(0.0, lfo.next(25, 100.0));
+        assert_eq!(1.0, lfo.next(50, 200.0));
+        assert_eq!("(Triangle, 96 ticks)", format!("{}", lfo));
+    }
+}
@@ -0,0 +1,228 @@
+use core::fmt;
+
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng};
+
+use crate::constants::{DESTINATIONS, LFOS, SOURCES};
+use crate::error::Error;
+use crate::snapshot::RouteState;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Source {
+    Lfo(usize),
+    Envelope(usize),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Destination {
+    Ratio(usize),
+    Level(usize),
+    Cutoff,
+    Q,
+    Pan,
+    Amplitude,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Route {
+    source: Source,
+    destination: Destination,
+    depth: f32,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Modulation {
+    pub ratios: [f32; 3],
+    pub levels: [f32; 3],
+    pub cutoff: f32,
+    pub q: f32,
+    pub pan: f32,
+    pub amplitude: f32,
+}
+
+impl Route {
+    pub fn new(source: Source, destination: Destination, depth: f32) -> Self {
+        Route {
+            source,
+            destination,
+            depth,
+        }
+    }
+
+    pub fn random(rng: &mut SmallRng) -> Result<Self, Error> {
+        let source = *SOURCES.choose(rng).ok_or(Error::Rng)?;
+        let (destination, depths) = DESTINATIONS.choose(rng).ok_or(Error::Rng)?;
+        let depth = rng.gen_range(depths.clone());
+
+        Ok(Self::new(source, *destination, depth))
+    }
+
+    pub fn restore(state: &RouteState) -> Result<Self, Error> {
+        Ok(Self::new(
+            Source::try_from(state.source)?,
+            Destination::try_from(state.destination)?,
+            state.depth,
+        ))
+    }
+
+    pub fn snapshot(&self) -> RouteState {
+        RouteState {
+            source: self.source.into(),
+            destination: self.destination.into(),
+            depth: self.depth,
+        }
+    }
+
+    pub fn apply(&self, lfos: &[f32; LFOS], envelopes: &[f32; 3], modulation: &mut Modulation) {
+        let amount = self.depth
+            * match self.source {
+                Source::Lfo(index) => lfos[index],
+                Source::Envelope(index) => envelopes[index],
+            };
+
+        match self.destination {
+            Destination::Ratio(index) => modulation.ratios[index] += amount,
+            Destination::Level(index) => modulation.levels[index] += amount,
+            Destination::Cutoff => modulation.cutoff += amount,
+            Destination::Q => modulation.q += amount,
+            Destination::Pan => modulation.pan += amount,
+            Destination::Amplitude => modulation.amplitude += amount,
+        }
+    }
+}
+
+impl From<Source> for u8 {
+    fn from(source: Source) -> u8 {
+        match source {
+            Source::Lfo(index) => index as u8,
+            Source::Envelope(index) => (LFOS + index) as u8,
+        }
+    }
+}
+
+impl TryFrom<u8> for Source {
+    type Error = Error;
+
+    fn try_from(source: u8) -> Result<Self, Error> {
+        match source as usize {
+            index if index < LFOS => Ok(Source::Lfo(index)),
+            index if index < LFOS + 3 => Ok(Source::Envelope(index - LFOS)),
+            _ => Err(Error::Snapshot),
+        }
+    }
+}
+
+impl From<Destination> for u8 {
+    fn from(destination: Destination) -> u8 {
+        match destination {
+            Destination::Ratio(index) => index as u8,
+            Destination::Level(index) => 3 + index as u8,
+            Destination::Cutoff => 6,
+            Destination::Q => 7,
+            Destination::Pan => 8,
+            Destination::Amplitude => 9,
+        }
+    }
+}
+
+impl TryFrom<u8> for Destination {
+    type Error = Error;
+
+    fn try_from(destination: u8) -> Result<Self, Error> {
+        match destination {
+            0..=2 => Ok(Destination::Ratio(destination as usize)),
+            3..=5 => Ok(Destination::Level(destination as usize - 3)),
+            6 => Ok(Destination::Cutoff),
+            7 => Ok(Destination::Q),
+            8 => Ok(Destination::Pan),
+            9 => Ok(Destination::Amplitude),
+            _ => Err(Error::Snapshot),
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Lfo(index) => write!(f, "LFO {}", index),
+            Source::Envelope(index) => write!(f, "ADSR {}", index),
+        }
+    }
+}
+
+impl fmt::Display for Destination {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Destination::Ratio(index) => write!(f, "Ratio {}", index),
+            Destination::Level(index) => write!(f, "Level {}", index),
+            Destination::Cutoff => write!(f, "Cutoff"),
+            Destination::Q => write!(f, "Q"),
+            Destination::Pan => write!(f, "Pan"),
+            Destination::Amplitude => write!(f, "Amplitude"),
+        }
+    }
+}
+
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "({} -> {}, {:.2})",
+            self.source, self.destination, self.depth
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn random() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let route = Route::random(&mut rng).unwrap();
+            assert!(SOURCES.contains(&route.source));
+            let (_, depths) = DESTINATIONS
+                .iter()
+                .find(|(destination, _)| *destination == route.destination)
+                .unwrap();
+            assert!(depths.contains(&route.depth));
+        }
+    }
+
+    #[test]
+    fn apply() {
+        let routes = [
+            Route::new(Source::Lfo(1), Destination::Cutoff, 2.0),
+            Route::new(Source::Envelope(2), Destination::Cutoff, -1.0),
+            Route::new(Source::Lfo(0), Destination::Level(1), 0.5),
+        ];
+        let mut modulation = Modulation::default();
+        for route in &routes {
+            route.apply(&[0.5, -0.25], &[0.0, 0.0, 0.75], &mut modulation);
+        }
+        assert_eq!(-1.25, modulation.cutoff);
+        assert_eq!([0.0, 0.25, 0.0], modulation.levels);
+        assert_eq!(0.0, modulation.amplitude);
+        assert_eq!("(LFO 1 -> Cutoff, 2.00)", format!("{}", routes[0]));
+    }
+
+    #[test]
+    fn encode() {
+        for source in 0..LFOS as u8 + 3 {
+            assert_eq!(source, u8::from(Source::try_from(source).unwrap()));
+        }
+        assert!(Source::try_from(LFOS as u8 + 3).is_err());
+
+        for destination in 0..10 {
+            let decoded = Destination::try_from(destination).unwrap();
+            assert_eq!(destination, u8::from(decoded));
+        }
+        assert!(Destination::try_from(10).is_err());
+
+        let route = Route::new(Source::Envelope(1), Destination::Pan, 0.3);
+        assert_eq!(route, Route::restore(&route.snapshot()).unwrap());
+    }
+}
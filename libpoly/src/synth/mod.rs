@@ -1,97 +1,748 @@
 mod adsr;
+mod algorithm;
 mod filter;
+mod lfo;
+mod matrix;
 mod operator;
+mod pan;
 mod signal;
+mod smooth;
+mod voice;
 
 use core::fmt;
-use rand::{rngs::SmallRng, Rng};
+use core::ops::RangeInclusive;
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng};
 
-pub use signal::Signal;
+pub use adsr::Timing;
+pub use algorithm::Algorithm;
+pub use filter::Response;
+pub use matrix::{Destination, Source};
+pub use signal::{Oscillator, Signal};
+pub use voice::Steal;
 
-use crate::clock::Clock;
-use crate::constants::SYNTH_MUTATE;
+use crate::clock::{interval, Clock};
+use crate::config::PolyConfig;
+use crate::constants::{
+    ALGORITHMS, BLOCK, CONTROL, CURVE, FEEDBACK, LFOS, PAN, POLYPHONY, ROUTES, STAGE, STEAL, VOICES,
+};
 use crate::error::Error;
 use crate::logger::{debug, error};
+use crate::snapshot::{EnvelopeState, OperatorState, SynthState};
+use crate::Frame;
 use adsr::ADSR;
 use filter::Filter;
+use lfo::Lfo;
+use matrix::Route;
 use operator::Operator;
+use pan::Pan;
+use voice::Voice;
 
 pub struct Synth {
     sample_rate: f32,
-    operators: [Operator; 3],
-    adsr: ADSR,
-    filter: Filter,
+    voices: [Voice; VOICES],
+    polyphony: usize,
+    steal: Steal,
+    legato: bool,
+    level: f32,
+    age: usize,
+    locked: bool,
+    rate: f32,
+    operators: &'static [(Signal, RangeInclusive<f32>)],
+    mutate: RangeInclusive<usize>,
     mutate_clock: Clock,
+    lfos: [Lfo; LFOS],
+    values: [f32; LFOS],
+    routes: [Route; ROUTES],
+    counter: usize,
+    buffer: [f32; BLOCK],
 }
 
 impl Synth {
-    pub fn new(rng: &mut SmallRng, sample_rate: f32) -> Result<Self, Error> {
+    pub fn new(
+        rng: &mut SmallRng,
+        sample_rate: f32,
+        level: f32,
+        config: &PolyConfig,
+    ) -> Result<Self, Error> {
         let operators = [
-            Operator::new(sample_rate, 1.0, Signal::Sine),
-            Operator::random(rng, sample_rate)?,
-            Operator::random(rng, sample_rate)?,
+            Operator::new(sample_rate, 1.0, Signal::Sine, Oscillator::PolyBlep),
+            Operator::random(rng, sample_rate, config.operators)?,
+            Operator::random(rng, sample_rate, config.operators)?,
         ];
 
-        let adsr = ADSR::new(rng);
+        let algorithm = ALGORITHMS.choose(rng).ok_or(Error::Rng)?;
+        let indices = algorithm.indices(rng);
+        let feedback = rng.gen_range(FEEDBACK);
 
-        let filter = Filter::new(rng, sample_rate);
+        let envelopes = [
+            ADSR::random(rng, sample_rate, config),
+            ADSR::random(rng, sample_rate, config),
+            ADSR::random(rng, sample_rate, config),
+        ];
+
+        let filter = Filter::random(rng, sample_rate, config)?;
+
+        let pan = Pan::random(rng, sample_rate);
+
+        let voice = Voice::new(
+            algorithm, indices, feedback, operators, envelopes, filter, pan,
+        );
+
+        let mutate_clock = Clock::deadline(rng.gen_range(config.synth_mutate.clone()));
 
-        let mutate_clock = Clock::deadline(rng.gen_range(SYNTH_MUTATE));
+        let lfos = [
+            Lfo::random(rng, sample_rate)?,
+            Lfo::random(rng, sample_rate)?,
+        ];
+        let routes = [
+            Route::random(rng)?,
+            Route::random(rng)?,
+            Route::random(rng)?,
+            Route::random(rng)?,
+        ];
 
         Ok(Synth {
             sample_rate,
-            operators,
-            adsr,
-            filter,
+            voices: core::array::from_fn(|_| voice.clone()),
+            polyphony: POLYPHONY,
+            steal: STEAL,
+            legato: false,
+            level,
+            age: 0,
+            locked: false,
+            rate: 1.0,
+            operators: config.operators,
+            mutate: config.synth_mutate.clone(),
             mutate_clock,
+            values: lfos.each_ref().map(Lfo::value),
+            lfos,
+            routes,
+            counter: 0,
+            buffer: [0.0; BLOCK],
         })
     }
 
-    pub fn play(&mut self, rng: &mut SmallRng, length: usize, frequency: f32) {
+    pub fn set_polyphony(&mut self, polyphony: usize, steal: Steal) -> Result<(), Error> {
+        if polyphony == 0 || polyphony > VOICES {
+            return Err(Error::Polyphony);
+        }
+
+        for voice in &mut self.voices[polyphony..] {
+            voice.stop();
+        }
+
+        self.polyphony = polyphony;
+        self.steal = steal;
+        Ok(())
+    }
+
+    pub fn set_legato(&mut self, legato: bool) {
+        self.legato = legato;
+    }
+
+    pub fn set_locked(&mut self, locked: bool) {
+        self.locked = locked;
+    }
+
+    pub fn set_rate(&mut self, rate: f32) {
+        self.mutate_clock.scale(self.rate / rate);
+        self.rate = rate;
+    }
+
+    pub fn set_filter(&mut self, cutoff: f32, q: f32) -> Result<(), Error> {
+        if !filter(self.sample_rate, cutoff, q) {
+            return Err(Error::Control);
+        }
+
+        for voice in &mut self.voices {
+            voice.set_filter(cutoff, q);
+        }
+        Ok(())
+    }
+
+    pub fn set_response(&mut self, response: Response) {
+        for voice in &mut self.voices {
+            voice.set_response(response);
+        }
+    }
+
+    pub fn set_envelope(
+        &mut self,
+        index: usize,
+        attack: f32,
+        decay: f32,
+        sustain: f32,
+        release: f32,
+    ) -> Result<(), Error> {
+        let timing = self.voices[0]
+            .envelope(index)
+            .ok_or(Error::Control)?
+            .timing();
+        if !(0.0..=1.0).contains(&sustain) || !stages(timing, attack, decay, release) {
+            return Err(Error::Control);
+        }
+
+        for voice in &mut self.voices {
+            voice.set_envelope(index, attack, decay, sustain, release);
+        }
+        Ok(())
+    }
+
+    pub fn set_curve(&mut self, index: usize, curve: f32, timing: Timing) -> Result<(), Error> {
+        let [attack, decay, release] = self.voices[0]
+            .envelope(index)
+            .ok_or(Error::Control)?
+            .stages();
+        if !CURVE.contains(&curve) || !stages(timing, attack, decay, release) {
+            return Err(Error::Control);
+        }
+
+        for voice in &mut self.voices {
+            voice.set_curve(index, curve, timing);
+        }
+        Ok(())
+    }
+
+    pub fn set_operator(
+        &mut self,
+        index: usize,
+        signal: Signal,
+        oscillator: Oscillator,
+        ratio: f32,
+    ) -> Result<(), Error> {
+        if index >= 3 || !positive(ratio) {
+            return Err(Error::Control);
+        }
+
+        let operator = Operator::new(self.sample_rate, ratio, signal, oscillator);
+        for voice in &mut self.voices {
+            voice.set_operator(index, &operator);
+        }
+        Ok(())
+    }
+
+    pub fn snapshot(&mut self) -> SynthState {
+        SynthState {
+            voice: self.voices[0].snapshot(),
+            age: self.age,
+            legato: self.legato,
+            locked: self.locked,
+            rate: self.rate,
+            mutate_clock: self.mutate_clock.snapshot(),
+            lfos: self.lfos.each_mut().map(|lfo| lfo.snapshot()),
+            routes: self.routes.map(|route| route.snapshot()),
+        }
+    }
+
+    pub fn restore(&mut self, state: &SynthState) -> Result<(), Error> {
+        let voice = &state.voice;
+        if !(0.0..1.0).contains(&voice.feedback)
+            || !voice
+                .indices
+                .iter()
+                .flatten()
+                .all(|index| index.is_finite())
+            || !voice
+                .operators
+                .iter()
+                .all(|operator| positive(operator.ratio))
+            || !voice
+                .envelopes
+                .iter()
+                .chain([&voice.filter.envelope])
+                .all(envelope)
+            || !filter(self.sample_rate, voice.filter.cutoff, voice.filter.q)
+            || !voice.filter.depth.is_finite()
+            || !(0.0..=1.0).contains(&voice.filter.tracking)
+            || !(0.0..=1.0).contains(&voice.filter.sensitivity)
+            || !(0.0..=1.0).contains(&voice.pan.depth)
+            || !positive(voice.pan.rate)
+            || !state.lfos.iter().all(|lfo| positive(lfo.rate))
+            || !state.routes.iter().all(|route| route.depth.is_finite())
+            || !positive(state.rate)
+        {
+            return Err(Error::Snapshot);
+        }
+
+        let algorithm = ALGORITHMS
+            .get(state.voice.algorithm)
+            .ok_or(Error::Snapshot)?;
+
+        let operator = |state: &OperatorState| -> Result<Operator, Error> {
+            let signal = Signal::try_from(state.signal)?;
+            let oscillator = Oscillator::try_from(state.oscillator)?;
+            Ok(Operator::new(
+                self.sample_rate,
+                state.ratio,
+                signal,
+                oscillator,
+            ))
+        };
+        let operators = [
+            operator(&state.voice.operators[0])?,
+            operator(&state.voice.operators[1])?,
+            operator(&state.voice.operators[2])?,
+        ];
+
+        let envelopes = [
+            ADSR::restore(self.sample_rate, &state.voice.envelopes[0])?,
+            ADSR::restore(self.sample_rate, &state.voice.envelopes[1])?,
+            ADSR::restore(self.sample_rate, &state.voice.envelopes[2])?,
+        ];
+
+        let mut filter = Filter::new(
+            self.sample_rate,
+            Response::try_from(state.voice.filter.response)?,
+            state.voice.filter.cutoff,
+            state.voice.filter.q,
+        );
+        filter.set_modulation(
+            ADSR::restore(self.sample_rate, &state.voice.filter.envelope)?,
+            state.voice.filter.depth,
+            state.voice.filter.tracking,
+            state.voice.filter.sensitivity,
+        );
+
+        let pan = Pan::new(
+            self.sample_rate,
+            state.voice.pan.depth,
+            state.voice.pan.rate,
+        );
+
+        let lfos = [
+            Lfo::restore(self.sample_rate, &state.lfos[0])?,
+            Lfo::restore(self.sample_rate, &state.lfos[1])?,
+        ];
+
+        let routes = [
+            Route::restore(&state.routes[0])?,
+            Route::restore(&state.routes[1])?,
+            Route::restore(&state.routes[2])?,
+            Route::restore(&state.routes[3])?,
+        ];
+
+        let voice = Voice::new(
+            algorithm,
+            state.voice.indices,
+            state.voice.feedback,
+            operators,
+            envelopes,
+            filter,
+            pan,
+        );
+
+        self.voices = core::array::from_fn(|_| voice.clone());
+        self.values = lfos.each_ref().map(Lfo::value);
+        self.lfos = lfos;
+        self.routes = routes;
+        self.age = state.age;
+        self.legato = state.legato;
+        self.locked = state.locked;
+        self.rate = state.rate;
+        self.mutate_clock.restore(&state.mutate_clock);
+        Ok(())
+    }
+
+    pub fn play(&mut self, rng: &mut SmallRng, length: usize, frequency: f32, velocity: f32) {
         if self.mutate_clock.ready() {
             self.mutate(rng);
         }
 
-        for operator in &mut self.operators {
-            operator.set_frequency(frequency);
+        let index = self.allocate(frequency);
+        self.age = self.age.wrapping_add(1);
+        let voice = &mut self.voices[index];
+        if self.legato && voice.gated() && voice.frequency() == frequency {
+            voice.extend(self.age, length);
+            voice.modulate(&self.routes, &self.values);
+        } else {
+            voice.start(self.age, length, frequency, velocity, rng.gen_range(PAN));
+            voice.modulate(&self.routes, &self.values);
+            voice.settle();
         }
+    }
+
+    pub fn render(&mut self, out: &mut [Frame], tempo: f32) {
+        self.mutate_clock.advance(out.len());
+
+        let mut start = 0;
+        while start < out.len() {
+            if self.counter == 0 {
+                self.modulate(tempo);
+            }
 
-        self.adsr.start(length);
+            let end = out.len().min(start + self.counter);
+            let frames = &mut out[start..end];
+            let buffer = &mut self.buffer[..frames.len()];
+            for voice in &mut self.voices {
+                if voice.active() {
+                    voice.render(buffer, frames, self.level);
+                }
+            }
+
+            self.counter -= end - start;
+            start = end;
+        }
     }
 
-    pub fn next(&mut self) -> f32 {
-        self.mutate_clock.tick();
+    fn modulate(&mut self, tempo: f32) {
+        self.counter = CONTROL;
+        for (value, lfo) in self.values.iter_mut().zip(&mut self.lfos) {
+            *value = lfo.next(CONTROL, tempo);
+        }
+        for voice in &mut self.voices {
+            if voice.active() {
+                voice.modulate(&self.routes, &self.values);
+            }
+        }
+    }
 
-        self.filter.generate(
-            self.operators
-                .iter_mut()
-                .fold(1.0, |sample, operator| sample * operator.next())
-                * self.adsr.next(),
-        )
+    fn allocate(&self, frequency: f32) -> usize {
+        let voices = &self.voices[..self.polyphony];
+        let retrigger = |voice: &Voice| match self.steal {
+            Steal::SamePitch => voice.active(),
+            _ => self.legato && voice.gated(),
+        };
+        voices
+            .iter()
+            .position(|voice| retrigger(voice) && voice.frequency() == frequency)
+            .or_else(|| voices.iter().position(|voice| !voice.active()))
+            .unwrap_or_else(|| self.steal.choose(voices, frequency))
     }
 
     fn mutate(&mut self, rng: &mut SmallRng) {
-        self.mutate_clock.reset(rng.gen_range(SYNTH_MUTATE));
+        self.mutate_clock
+            .reset(interval(rng.gen_range(self.mutate.clone()), self.rate));
+        if self.locked {
+            return;
+        }
 
         let index = rng.gen_range(1..=2);
-        if let Ok(operator) = Operator::random(rng, self.sample_rate) {
+        if let Ok(operator) = Operator::random(rng, self.sample_rate, self.operators) {
             debug!("[OPERATOR {}] {}", index, operator);
-            self.operators[index] = operator;
+            for voice in &mut self.voices {
+                voice.set_operator(index, &operator);
+            }
         } else {
             error!("Operator mutate failed")
         }
     }
 }
 
+fn stages(timing: Timing, attack: f32, decay: f32, release: f32) -> bool {
+    let stages = [attack, decay, release];
+    match timing {
+        Timing::Relative => {
+            stages.iter().all(|stage| (0.0..=1.0).contains(stage))
+                && attack + decay + release <= 1.0
+        }
+        Timing::Absolute => stages.iter().all(|stage| (0.0..=STAGE).contains(stage)),
+    }
+}
+
+fn envelope(state: &EnvelopeState) -> bool {
+    Timing::try_from(state.timing).is_ok_and(|timing| {
+        stages(timing, state.attack, state.decay, state.release)
+            && (0.0..=1.0).contains(&state.sustain)
+            && CURVE.contains(&state.curve)
+    })
+}
+
+fn filter(sample_rate: f32, cutoff: f32, q: f32) -> bool {
+    cutoff > 0.0 && cutoff < sample_rate / 2.0 && (0.0..=1.0).contains(&q)
+}
+
+fn positive(value: f32) -> bool {
+    value > 0.0 && value.is_finite()
+}
+
 impl fmt::Display for Synth {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "[FILTER] {} [ADSR] {} [OPERATORS] {}, {}, {}",
-            self.filter, self.adsr, self.operators[0], self.operators[1], self.operators[2]
+            "{} [LFO] {}, {} [ROUTES] {}, {}, {}, {} [VOICES] {} ({}) [LEVEL] {:.2}",
+            self.voices[0],
+            self.lfos[0],
+            self.lfos[1],
+            self.routes[0],
+            self.routes[1],
+            self.routes[2],
+            self.routes[3],
+            self.polyphony,
+            self.steal,
+            self.level
         )
     }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use rand::SeedableRng;
+
+    use super::lfo::Shape;
+    use super::*;
+
+    fn synth(polyphony: usize, steal: Steal) -> (SmallRng, Synth) {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut synth = Synth::new(&mut rng, 44100.0, 1.0, &PolyConfig::default()).unwrap();
+        synth.set_polyphony(polyphony, steal).unwrap();
+        (rng, synth)
+    }
+
+    fn frequencies(synth: &Synth) -> Vec<f32> {
+        synth.voices[..synth.polyphony]
+            .iter()
+            .map(|voice| voice.frequency())
+            .collect()
+    }
+
+    #[test]
+    fn allocate() {
+        let (mut rng, mut synth) = synth(4, Steal::Oldest);
+        for frequency in [100.0, 200.0, 300.0] {
+            synth.play(&mut rng, 1000, frequency, 1.0);
+        }
+
+        assert_eq!(vec![100.0, 200.0, 300.0, 0.0], frequencies(&synth));
+        assert_eq!(
+            vec![true, true, true, false],
+            synth.voices[..4]
+                .iter()
+                .map(|voice| voice.active())
+                .collect::<Vec<bool>>()
+        );
+    }
+
+    #[test]
+    fn steal_oldest() {
+        let (mut rng, mut synth) = synth(2, Steal::Oldest);
+        for frequency in [100.0, 200.0, 300.0, 400.0] {
+            synth.play(&mut rng, 1000, frequency, 1.0);
+        }
+
+        assert_eq!(vec![300.0, 400.0], frequencies(&synth));
+    }
+
+    #[test]
+    fn steal_quietest() {
+        let (mut rng, mut synth) = synth(2, Steal::Quietest);
+        let mut out = [[0.0; 2]; 100];
+
+        synth.play(&mut rng, 1000, 100.0, 1.0);
+        synth.render(&mut out, 44100.0);
+        synth.play(&mut rng, 1000, 200.0, 1.0);
+        synth.render(&mut out[..1], 44100.0);
+        synth.play(&mut rng, 1000, 300.0, 1.0);
+
+        assert_eq!(vec![100.0, 300.0], frequencies(&synth));
+    }
+
+    #[test]
+    fn steal_same_pitch() {
+        let (mut rng, mut synth) = synth(2, Steal::SamePitch);
+        for frequency in [100.0, 200.0, 200.0] {
+            synth.play(&mut rng, 1000, frequency, 1.0);
+        }
+        assert_eq!(vec![100.0, 200.0], frequencies(&synth));
+        assert_eq!(3, synth.voices[1].age());
+
+        synth.play(&mut rng, 1000, 300.0, 1.0);
+        assert_eq!(vec![300.0, 200.0], frequencies(&synth));
+    }
+
+    #[test]
+    fn same_pitch_free() {
+        let (mut rng, mut synth) = synth(4, Steal::SamePitch);
+        for frequency in [100.0, 200.0, 100.0] {
+            synth.play(&mut rng, 1000, frequency, 1.0);
+        }
+        assert_eq!(vec![100.0, 200.0, 0.0, 0.0], frequencies(&synth));
+        assert_eq!(3, synth.voices[0].age());
+    }
+
+    #[test]
+    fn restore() {
+        let (mut rng, mut synth) = synth(2, Steal::Oldest);
+        synth.play(&mut rng, 1000, 100.0, 1.0);
+        let state = synth.snapshot();
+
+        let mut restored = Synth::new(&mut rng, 44100.0, 1.0, &PolyConfig::default()).unwrap();
+        restored.restore(&state).unwrap();
+        assert_eq!(synth.snapshot(), restored.snapshot());
+        assert_eq!(
+            format!("{}", synth.voices[0]),
+            format!("{}", restored.voices[0])
+        );
+        assert!(restored.voices.iter().all(|voice| !voice.active()));
+
+        let mut invalid = state;
+        invalid.voice.operators[1].signal = 3;
+        assert!(restored.restore(&invalid).is_err());
+
+        let mut invalid = state;
+        invalid.voice.operators[2].oscillator = 2;
+        assert!(restored.restore(&invalid).is_err());
+
+        let mut invalid = state;
+        invalid.routes[2].destination = 10;
+        assert!(restored.restore(&invalid).is_err());
+
+        let mut invalid = state;
+        invalid.voice.filter.envelope.timing = 2;
+        assert!(restored.restore(&invalid).is_err());
+
+        let mut invalid = state;
+        invalid.voice.filter.cutoff = 22050.0;
+        assert!(restored.restore(&invalid).is_err());
+
+        let mut invalid = state;
+        invalid.voice.operators[1].ratio = 0.0;
+        assert!(restored.restore(&invalid).is_err());
+
+        let mut invalid = state;
+        invalid.voice.envelopes[0].timing = Timing::Relative as u8;
+        invalid.voice.envelopes[0].attack = 0.5;
+        invalid.voice.envelopes[0].decay = 0.3;
+        invalid.voice.envelopes[0].release = 0.3;
+        assert!(restored.restore(&invalid).is_err());
+
+        let mut invalid = state;
+        invalid.lfos[1].rate = f32::NAN;
+        assert!(restored.restore(&invalid).is_err());
+    }
+
+    #[test]
+    fn modulation() {
+        let render = |depth: f32, chunk: usize| {
+            let (mut rng, mut synth) = synth(1, Steal::Oldest);
+            synth.lfos[0] = Lfo::new(44100.0, Shape::Triangle, 10.0, 0, 0);
+            synth.values[0] = synth.lfos[0].value();
+            synth.routes = [Route::new(Source::Lfo(0), Destination::Amplitude, depth); ROUTES];
+            synth.play(&mut rng, 10_000, 100.0, 1.0);
+
+            let mut out = vec![[0.0; 2]; 4410];
+            for frames in out.chunks_mut(chunk) {
+                synth.render(frames, 44100.0);
+            }
+            out
+        };
+        let energy = |frames: &[Frame]| frames.iter().map(|frame| frame[0] * frame[0]).sum::<f32>();
+
+        let dry = render(0.0, BLOCK);
+        let wet = render(0.25, BLOCK);
+        assert!(energy(&wet[..CONTROL]) < energy(&dry[..CONTROL]) * 0.01);
+        assert!(energy(&wet[2200..2400]) > energy(&dry[2200..2400]) * 3.0);
+        assert_eq!(wet, render(0.25, 61));
+    }
+
+    #[test]
+    fn controls() {
+        let (mut rng, mut synth) = synth(2, Steal::Oldest);
+        synth.set_filter(2000.0, 0.25).unwrap();
+        synth.set_envelope(1, 0.1, 0.2, 0.3, 0.4).unwrap();
+        synth
+            .set_operator(2, Signal::Saw, Oscillator::Naive, 0.5)
+            .unwrap();
+        synth.play(&mut rng, 1000, 100.0, 1.0);
+
+        let state = synth.voices[1].snapshot();
+        assert_eq!(2000.0, state.filter.cutoff);
+        assert_eq!(0.25, state.filter.q);
+        assert_eq!(0.3, state.envelopes[1].sustain);
+        assert_eq!(Signal::Saw as u8, state.operators[2].signal);
+        assert_eq!(Oscillator::Naive as u8, state.operators[2].oscillator);
+        assert_eq!(0.5, state.operators[2].ratio);
+
+        assert!(synth.set_filter(0.0, 0.25).is_err());
+        assert!(synth.set_filter(22050.0, 0.25).is_err());
+        assert!(synth.set_filter(2000.0, 1.5).is_err());
+        assert!(synth.set_envelope(3, 0.1, 0.2, 0.3, 0.4).is_err());
+        assert!(synth.set_envelope(0, 0.5, 0.5, 0.3, 0.5).is_err());
+        assert!(synth.set_envelope(0, 0.1, 0.2, 1.3, 0.4).is_err());
+        assert!(synth
+            .set_operator(3, Signal::Sine, Oscillator::PolyBlep, 1.0)
+            .is_err());
+        assert!(synth
+            .set_operator(1, Signal::Sine, Oscillator::PolyBlep, 0.0)
+            .is_err());
+    }
+
+    #[test]
+    fn curve() {
+        let (_, mut synth) = synth(2, Steal::Oldest);
+        synth.set_curve(2, 1.5, Timing::Absolute).unwrap();
+        synth.set_envelope(2, 0.5, 2.0, 0.5, 4.0).unwrap();
+
+        let state = synth.voices[1].snapshot();
+        assert_eq!(1.5, state.envelopes[2].curve);
+        assert_eq!(Timing::Absolute as u8, state.envelopes[2].timing);
+        assert_eq!(4.0, state.envelopes[2].release);
+
+        assert!(synth.set_curve(3, 1.5, Timing::Absolute).is_err());
+        assert!(synth.set_curve(2, 5.0, Timing::Absolute).is_err());
+        assert!(synth.set_curve(2, 1.5, Timing::Relative).is_err());
+        assert!(synth.set_envelope(2, 0.5, 2.0, 0.5, STAGE + 1.0).is_err());
+    }
+
+    #[test]
+    fn legato() {
+        let (mut rng, mut synth) = synth(2, Steal::Oldest);
+        synth.set_legato(true);
+        synth.play(&mut rng, 1000, 100.0, 1.0);
+
+        let mut out = [[0.0; 2]; 500];
+        synth.render(&mut out, 44100.0);
+        let level = synth.voices[0].level();
+
+        synth.play(&mut rng, 1000, 100.0, 1.0);
+        assert_eq!(vec![100.0, 0.0], frequencies(&synth));
+        assert_eq!(2, synth.voices[0].age());
+        assert_eq!(level, synth.voices[0].level());
+
+        synth.render(&mut out, 44100.0);
+        assert!(synth.voices[0].gated());
+
+        synth.set_legato(false);
+        synth.play(&mut rng, 1000, 100.0, 1.0);
+        assert_eq!(vec![100.0, 100.0], frequencies(&synth));
+    }
+
+    #[test]
+    fn locked() {
+        let (mut rng, mut synth) = synth(1, Steal::Oldest);
+        synth.set_locked(true);
+        let expected = synth.voices[0].snapshot();
+
+        synth.mutate_clock.reset(0);
+        synth.play(&mut rng, 1000, 100.0, 1.0);
+        assert_eq!(expected, synth.voices[0].snapshot());
+        assert!(!synth.mutate_clock.ready());
+    }
+
+    #[test]
+    fn set_polyphony() {
+        let (_, mut synth) = synth(1, Steal::Oldest);
+        assert!(synth.set_polyphony(0, Steal::Oldest).is_err());
+        assert!(synth.set_polyphony(VOICES + 1, Steal::Oldest).is_err());
+        assert!(synth.set_polyphony(VOICES, Steal::Quietest).is_ok());
+        assert_eq!(VOICES, synth.polyphony);
+        assert_eq!(Steal::Quietest, synth.steal);
+    }
+
+    #[test]
+    fn shrink_polyphony() {
+        let (mut rng, mut synth) = synth(4, Steal::Oldest);
+        for frequency in [100.0, 200.0, 300.0, 400.0] {
+            synth.play(&mut rng, 100_000, frequency, 1.0);
+        }
+        synth.set_polyphony(2, Steal::Oldest).unwrap();
+        assert!(synth.voices[..2].iter().all(|voice| voice.gated()));
+        assert!(synth.voices[2..4].iter().all(|voice| !voice.gated()));
+
+        let mut out = [[0.0; 2]; BLOCK];
+        for _ in 0..100 {
+            synth.render(&mut out, 44100.0);
+        }
+        assert!(synth.voices[..2].iter().all(|voice| voice.active()));
+        assert!(synth.voices[2..4].iter().all(|voice| !voice.active()));
+    }
+}
@@ -1,98 +1,256 @@
 use core::fmt;
+use core::ops::RangeInclusive;
 
+use libm::{exp2f, floorf};
 use rand::{rngs::SmallRng, seq::SliceRandom, Rng};
 
-use super::signal::Signal;
-use crate::constants::OPERATORS;
+use super::signal::{Oscillator, Signal};
+use super::smooth::Smooth;
+use crate::constants::OSCILLATORS;
 use crate::error::Error;
+use crate::snapshot::OperatorState;
 
+const PHASE: f32 = 4_294_967_296.0;
+const UNIT: f32 = 16_777_216.0;
+
+#[derive(Clone)]
 pub struct Operator {
     sample_rate: f32,
+    note: f32,
     frequency: f32,
-    ratio: f32,
+    ratio: Smooth,
+    shift: f32,
     signal: Signal,
-    clock: usize,
+    oscillator: Oscillator,
+    phase: u32,
+    increment: u32,
 }
 
 impl Operator {
-    pub fn new(sample_rate: f32, ratio: f32, signal: Signal) -> Self {
+    pub fn new(sample_rate: f32, ratio: f32, signal: Signal, oscillator: Oscillator) -> Self {
         Operator {
             sample_rate,
+            note: 0.0,
             frequency: 0.0,
-            ratio,
+            ratio: Smooth::new(ratio),
+            shift: 0.0,
             signal,
-            clock: 0,
+            oscillator,
+            phase: 0,
+            increment: 0,
         }
     }
 
-    pub fn random(rng: &mut SmallRng, sample_rate: f32) -> Result<Self, Error> {
-        let (signal, ratio_range) = OPERATORS.choose(rng).ok_or(Error::Rng)?;
+    pub fn random(
+        rng: &mut SmallRng,
+        sample_rate: f32,
+        operators: &[(Signal, RangeInclusive<f32>)],
+    ) -> Result<Self, Error> {
+        let (signal, ratio_range) = operators.choose(rng).ok_or(Error::Rng)?;
         let ratio = rng.gen_range(ratio_range.clone());
+        let oscillator = *OSCILLATORS.choose(rng).ok_or(Error::Rng)?;
+
+        Ok(Self::new(sample_rate, ratio, *signal, oscillator))
+    }
+
+    pub fn snapshot(&self) -> OperatorState {
+        OperatorState {
+            signal: self.signal as u8,
+            oscillator: self.oscillator as u8,
+            ratio: self.ratio.target(),
+        }
+    }
 
-        Ok(Self::new(sample_rate, ratio, *signal))
+    pub fn set(&mut self, operator: &Operator) {
+        self.signal = operator.signal;
+        self.oscillator = operator.oscillator;
+        self.ratio.set(operator.ratio.target());
+    }
+
+    pub fn settle(&mut self) {
+        self.ratio.settle();
     }
 
     pub fn set_frequency(&mut self, new_frequency: f32) {
-        self.frequency = new_frequency * self.ratio;
+        self.note = new_frequency;
+        self.frequency = new_frequency * self.ratio.value();
+        self.retune();
+    }
+
+    pub fn set_shift(&mut self, octaves: f32) {
+        if octaves != self.shift {
+            self.shift = octaves;
+            self.retune();
+        }
     }
 
-    pub fn next(&mut self) -> f32 {
-        self.clock = self.clock.wrapping_add(1);
-        self.signal
-            .generate(self.sample_rate, self.frequency, self.clock as f32)
+    fn retune(&mut self) {
+        let increment = self.frequency * exp2f(self.shift) / self.sample_rate;
+        self.increment = ((increment - floorf(increment)) * PHASE) as u32;
     }
+
+    pub fn next(&mut self, modulation: f32) -> f32 {
+        if self.ratio.active() {
+            self.frequency = self.note * self.ratio.next();
+            self.retune();
+        }
+        self.phase = self.phase.wrapping_add(self.increment);
+
+        let offset = ((modulation - floorf(modulation)) * PHASE) as u32;
+        self.signal.generate(
+            self.oscillator,
+            unit(self.phase.wrapping_add(offset)),
+            unit(self.increment),
+        )
+    }
+}
+
+fn unit(phase: u32) -> f32 {
+    (phase >> 8) as f32 / UNIT
 }
 
 impl fmt::Display for Operator {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "({}, {})", self.signal, self.ratio)
+        write!(
+            f,
+            "({}, {}, {})",
+            self.signal,
+            self.oscillator,
+            self.ratio.target()
+        )
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use core::f32::consts::PI;
+    use core::f32::consts::{PI, TAU};
 
     use rand::SeedableRng;
 
     use super::*;
+    use crate::constants::{OPERATORS, SMOOTH};
 
     #[test]
     fn random() {
         let mut rng = SmallRng::seed_from_u64(0);
 
-        let result = Operator::random(&mut rng, 1.0);
+        let result = Operator::random(&mut rng, 1.0, &OPERATORS);
         assert!(result.is_ok());
 
         let operator = result.unwrap();
         assert_eq!(1.0, operator.sample_rate);
-        assert_eq!(2.8782806, operator.ratio);
+        assert_eq!(2.8782806, operator.ratio.target());
         assert_eq!(Signal::Square, operator.signal);
+        assert!(OSCILLATORS.contains(&operator.oscillator));
     }
 
     #[test]
     fn set_frequency() {
-        let mut operator = Operator::new(1.0, 0.2, Signal::Sine);
+        let mut operator = Operator::new(1.0, 0.2, Signal::Sine, Oscillator::Naive);
         operator.set_frequency(440.0);
         assert_eq!(88.0, operator.frequency);
     }
 
+    #[test]
+    fn shift() {
+        let mut operator = Operator::new(8.0, 1.0, Signal::Sine, Oscillator::Naive);
+        operator.set_frequency(1.0);
+        operator.set_shift(1.0);
+        assert_eq!(1.0, operator.next(0.0));
+
+        operator.set_shift(0.0);
+        operator.set_frequency(4.0);
+        assert_eq!(-1.0, operator.next(0.0));
+    }
+
     #[test]
     fn next() {
-        let mut operator = Operator::new(1.0, 1.0, Signal::Sine);
+        let mut operator = Operator::new(1.0, 1.0, Signal::Sine, Oscillator::Naive);
         operator.set_frequency(PI);
         assert_eq!(
-            (0..8).map(|_| operator.next()).collect::<Vec<f32>>(),
+            (0..8).map(|_| operator.next(0.0)).collect::<Vec<f32>>(),
             vec![
-                0.77685404,
-                0.97834,
-                0.45523128,
-                -0.40504146,
-                -0.9653236,
-                -0.8106515,
-                -0.05557911,
-                0.74065745
+                0.77685356,
+                0.9783403,
+                0.4552316,
+                -0.40503865,
+                -0.9653222,
+                -0.8106519,
+                -0.0555823,
+                0.7406533
             ]
         );
     }
+
+    #[test]
+    fn modulation() {
+        let mut operator = Operator::new(8.0, 1.0, Signal::Sine, Oscillator::Naive);
+        operator.set_frequency(1.0);
+        assert_eq!(
+            vec![1.0, 0.0, -0.70710653, 1.0],
+            [0.125, -0.25, 0.5, 1.75]
+                .iter()
+                .map(|&modulation| operator.next(modulation))
+                .collect::<Vec<f32>>()
+        );
+    }
+
+    #[test]
+    fn retune() {
+        let mut operator = Operator::new(100.0, 1.0, Signal::Sine, Oscillator::Naive);
+        operator.set_frequency(3.0);
+        let mut samples = (0..7).map(|_| operator.next(0.0)).collect::<Vec<f32>>();
+
+        operator.set_frequency(11.0);
+        samples.extend((0..7).map(|_| operator.next(0.0)));
+
+        let limit = TAU * 11.0 / 100.0;
+        assert!(samples.windows(2).all(|w| (w[1] - w[0]).abs() <= limit));
+        assert!((unit(operator.phase) - 0.98).abs() < 1e-6);
+    }
+
+    #[test]
+    fn set() {
+        let mut operator = Operator::new(100.0, 1.0, Signal::Sine, Oscillator::Naive);
+        operator.set_frequency(3.0);
+        let mut samples = (0..7).map(|_| operator.next(0.0)).collect::<Vec<f32>>();
+
+        operator.set(&Operator::new(
+            100.0,
+            4.0,
+            Signal::Sine,
+            Oscillator::PolyBlep,
+        ));
+        assert_eq!(3.0, operator.frequency);
+        samples.extend((0..SMOOTH * 2).map(|_| operator.next(0.0)));
+
+        let limit = TAU * 12.0 / 100.0;
+        assert!(samples.windows(2).all(|w| (w[1] - w[0]).abs() <= limit));
+        assert_eq!(12.0, operator.frequency);
+        assert_eq!(Oscillator::PolyBlep, operator.oscillator);
+        assert_eq!(4.0, operator.snapshot().ratio);
+    }
+
+    #[test]
+    fn pitch() {
+        const HOURS: usize = 6;
+        const SAMPLE_RATE: usize = 1000;
+        const FREQUENCY: f32 = 123.45;
+
+        let mut operator = Operator::new(SAMPLE_RATE as f32, 1.0, Signal::Saw, Oscillator::Naive);
+        operator.set_frequency(FREQUENCY);
+
+        let mut cycles = 0;
+        let mut previous = operator.next(0.0);
+        for _ in 1..HOURS * 60 * 60 * SAMPLE_RATE {
+            let sample = operator.next(0.0);
+            if sample > previous {
+                cycles += 1;
+            }
+            previous = sample;
+        }
+
+        let expected = FREQUENCY as f64 * (HOURS * 60 * 60) as f64;
+        assert!((cycles as f64 - expected).abs() <= 1.0);
+    }
 }
@@ -0,0 +1,164 @@
+use core::f32::consts::{FRAC_PI_4, TAU};
+use core::fmt;
+
+use libm::{cosf, sinf};
+use rand::{rngs::SmallRng, Rng};
+
+use crate::constants::{AUTOPAN, AUTOPAN_DEPTH, AUTOPAN_RATE, CONTROL};
+use crate::snapshot::PanState;
+use crate::Frame;
+
+#[derive(Clone)]
+pub struct Pan {
+    sample_rate: f32,
+    position: f32,
+    shift: f32,
+    depth: f32,
+    rate: f32,
+    phase: f32,
+    gains: Frame,
+    target: Frame,
+    delta: Frame,
+    counter: usize,
+}
+
+impl Pan {
+    pub fn new(sample_rate: f32, depth: f32, rate: f32) -> Self {
+        Pan {
+            sample_rate,
+            position: 0.0,
+            shift: 0.0,
+            depth,
+            rate,
+            phase: 0.0,
+            gains: gains(0.0),
+            target: gains(0.0),
+            delta: [0.0; 2],
+            counter: 0,
+        }
+    }
+
+    pub fn random(rng: &mut SmallRng, sample_rate: f32) -> Self {
+        let depth = if rng.gen_bool(AUTOPAN) {
+            rng.gen_range(AUTOPAN_DEPTH)
+        } else {
+            0.0
+        };
+        let rate = rng.gen_range(AUTOPAN_RATE);
+
+        Self::new(sample_rate, depth, rate)
+    }
+
+    pub fn set_position(&mut self, position: f32) {
+        self.position = position;
+    }
+
+    pub fn set_shift(&mut self, shift: f32) {
+        self.shift = shift;
+    }
+
+    pub fn snapshot(&self) -> PanState {
+        PanState {
+            depth: self.depth,
+            rate: self.rate,
+        }
+    }
+
+    pub fn render(&mut self, input: &[f32], out: &mut [Frame]) {
+        for (sample, frame) in input.iter().zip(out.iter_mut()) {
+            if self.counter == 0 {
+                self.update();
+            }
+            self.counter -= 1;
+
+            frame[0] += sample * self.gains[0];
+            frame[1] += sample * self.gains[1];
+            self.gains[0] += self.delta[0];
+            self.gains[1] += self.delta[1];
+        }
+    }
+
+    fn update(&mut self) {
+        self.gains = self.target;
+
+        self.phase += self.rate * CONTROL as f32 / self.sample_rate;
+        if self.phase >= 1.0 {
+            self.phase -= 1.0;
+        }
+
+        self.target = gains(self.position + self.shift + self.depth * sinf(TAU * self.phase));
+        self.delta = [
+            (self.target[0] - self.gains[0]) / CONTROL as f32,
+            (self.target[1] - self.gains[1]) / CONTROL as f32,
+        ];
+        self.counter = CONTROL;
+    }
+}
+
+fn gains(position: f32) -> Frame {
+    let angle = (position.clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+    [cosf(angle), sinf(angle)]
+}
+
+impl fmt::Display for Pan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({:.2}, {:.2}Hz)", self.depth, self.rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn centre() {
+        let mut pan = Pan::new(1.0, 0.0, 0.0);
+        let mut out = [[0.0; 2]; 2];
+        pan.render(&[1.0, 1.0], &mut out);
+        assert_eq!(out, [[0.70710677, 0.70710677], [0.70710677, 0.70710677]]);
+    }
+
+    #[test]
+    fn position() {
+        let mut pan = Pan::new(1.0, 0.0, 0.0);
+        pan.set_position(-1.0);
+
+        let mut out = [[0.0; 2]; CONTROL * 2 + 1];
+        pan.render(&[1.0; CONTROL * 2 + 1], &mut out);
+        assert_eq!(out[0], gains(0.0));
+        assert_eq!(out[CONTROL], [1.0, 0.0]);
+        assert!(out[..=CONTROL].windows(2).all(|w| w[0][0] < w[1][0]));
+        assert!(out[..=CONTROL].windows(2).all(|w| w[0][1] > w[1][1]));
+
+        pan.set_position(1.0);
+        let mut out = [[0.5; 2]; 1];
+        pan.render(&[1.0], &mut out);
+        assert_eq!(out[0], [1.5, 0.5]);
+    }
+
+    #[test]
+    fn autopan() {
+        let mut pan = Pan::new(CONTROL as f32 * 4.0, 1.0, 1.0);
+
+        let mut out = [[0.0; 2]; CONTROL * 3 + 1];
+        pan.render(&[1.0; CONTROL * 3 + 1], &mut out);
+        assert_eq!(out[CONTROL], gains(1.0));
+        assert_eq!(out[CONTROL * 3], gains(-1.0));
+    }
+
+    #[test]
+    fn shift() {
+        let mut pan = Pan::new(1.0, 0.0, 0.0);
+        pan.set_position(0.5);
+        pan.set_shift(-1.5);
+
+        let mut out = [[0.0; 2]; CONTROL + 1];
+        pan.render(&[1.0; CONTROL + 1], &mut out);
+        assert_eq!(out[CONTROL], gains(-1.0));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!("(0.50, 0.10Hz)", format!("{}", Pan::new(1.0, 0.5, 0.1)));
+    }
+}
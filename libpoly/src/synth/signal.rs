@@ -3,6 +3,8 @@ use core::fmt;
 
 use libm::{atanf, sinf, tanf};
 
+use crate::error::Error;
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Signal {
     Sine,
@@ -10,22 +12,80 @@ pub enum Signal {
     Saw,
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Oscillator {
+    Naive,
+    PolyBlep,
+}
+
 impl Signal {
-    pub fn generate(&self, sample_rate: f32, frequency: f32, clock: f32) -> f32 {
-        match self {
-            Signal::Sine => sinf(TAU * frequency * (clock / sample_rate)),
-            Signal::Square => {
-                if (clock / sample_rate) as usize % frequency as usize == 0 {
-                    1.0
+    pub fn generate(&self, oscillator: Oscillator, phase: f32, increment: f32) -> f32 {
+        match (self, oscillator) {
+            (Signal::Sine, _) => sinf(TAU * phase),
+            (Signal::Square, Oscillator::Naive) => square(phase),
+            (Signal::Square, Oscillator::PolyBlep) => {
+                let shifted = if phase < 0.5 {
+                    phase + 0.5
                 } else {
-                    -1.0
-                }
+                    phase - 0.5
+                };
+                square(phase) + blep(phase, increment) - blep(shifted, increment)
             }
-            Signal::Saw => atanf(1.0 / tanf(FRAC_2_PI * (frequency * (PI * clock) / sample_rate))),
+            (Signal::Saw, Oscillator::Naive) => atanf(1.0 / tanf(FRAC_2_PI * (PI * phase))),
+            (Signal::Saw, Oscillator::PolyBlep) => saw(phase) - blep(phase, increment),
+        }
+    }
+}
+
+impl TryFrom<u8> for Oscillator {
+    type Error = Error;
+
+    fn try_from(oscillator: u8) -> Result<Self, Error> {
+        match oscillator {
+            0 => Ok(Oscillator::Naive),
+            1 => Ok(Oscillator::PolyBlep),
+            _ => Err(Error::Snapshot),
+        }
+    }
+}
+
+impl TryFrom<u8> for Signal {
+    type Error = Error;
+
+    fn try_from(signal: u8) -> Result<Self, Error> {
+        match signal {
+            0 => Ok(Signal::Sine),
+            1 => Ok(Signal::Square),
+            2 => Ok(Signal::Saw),
+            _ => Err(Error::Snapshot),
         }
     }
 }
 
+fn square(phase: f32) -> f32 {
+    if phase < 0.5 {
+        1.0
+    } else {
+        -1.0
+    }
+}
+
+fn saw(phase: f32) -> f32 {
+    2.0 * phase - 1.0
+}
+
+fn blep(phase: f32, increment: f32) -> f32 {
+    if phase < increment {
+        let t = phase / increment;
+        2.0 * t - t * t - 1.0
+    } else if phase > 1.0 - increment {
+        let t = (phase - 1.0) / increment;
+        t * t + 2.0 * t + 1.0
+    } else {
+        0.0
+    }
+}
+
 impl fmt::Display for Signal {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -40,46 +100,138 @@ impl fmt::Display for Signal {
     }
 }
 
+impl fmt::Display for Oscillator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Oscillator::Naive => "Naive",
+                Oscillator::PolyBlep => "PolyBlep",
+            }
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::f64::consts::TAU;
+
     use super::*;
 
+    fn generate(signal: Signal, oscillator: Oscillator, increment: f32, length: usize) -> Vec<f32> {
+        (0..length)
+            .map(|index| signal.generate(oscillator, (index as f32 * increment) % 1.0, increment))
+            .collect()
+    }
+
+    const LENGTH: usize = 2048;
+    const FUNDAMENTAL: usize = 93;
+    const INCREMENT: f32 = FUNDAMENTAL as f32 / LENGTH as f32;
+
+    fn aliasing(samples: Vec<f32>) -> f64 {
+        let (mut harmonic, mut aliased) = (0.0, 0.0);
+        for bin in 1..LENGTH / 2 {
+            let (re, im) = samples
+                .iter()
+                .enumerate()
+                .fold((0.0, 0.0), |(re, im), (n, x)| {
+                    let angle = TAU * ((bin * n) % LENGTH) as f64 / LENGTH as f64;
+                    (re + *x as f64 * angle.cos(), im - *x as f64 * angle.sin())
+                });
+
+            if bin % FUNDAMENTAL == 0 {
+                harmonic += re * re + im * im;
+            } else {
+                aliased += re * re + im * im;
+            }
+        }
+
+        aliased / harmonic
+    }
+
     #[test]
     fn sine() {
-        let signal = Signal::Sine;
         assert_eq!(
-            (0..4)
-                .map(|index| signal.generate(8.0, 1.0, index as f32))
-                .collect::<Vec<f32>>(),
+            generate(Signal::Sine, Oscillator::Naive, 0.125, 4),
             vec![0.0, 0.70710677, 1.0, 0.70710677]
         )
     }
 
     #[test]
     fn square() {
-        let signal = Signal::Square;
         assert_eq!(
-            (0..9)
-                .map(|index| signal.generate(4.0, 2.0, index as f32))
-                .collect::<Vec<f32>>(),
+            generate(Signal::Square, Oscillator::Naive, 0.125, 9),
             vec![1.0, 1.0, 1.0, 1.0, -1.0, -1.0, -1.0, -1.0, 1.0]
         )
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn saw() {
-        let signal = Signal::Saw;
         assert_eq!(
-            (0..5)
-                .map(|index| signal.generate(8.0, 1.0, index as f32))
-                .collect::<Vec<f32>>(),
+            generate(Signal::Saw, Oscillator::Naive, 0.125, 5),
             vec![1.5707963, 1.3207964, 1.0707964, 0.8207963, 0.5707963]
         )
     }
 
+    #[test]
+    fn square_blep() {
+        assert_eq!(
+            generate(Signal::Square, Oscillator::PolyBlep, 0.125, 9),
+            vec![0.0, 1.0, 1.0, 1.0, 0.0, -1.0, -1.0, -1.0, 0.0]
+        )
+    }
+
+    #[test]
+    fn saw_blep() {
+        assert_eq!(
+            generate(Signal::Saw, Oscillator::PolyBlep, 0.125, 5),
+            vec![0.0, -0.75, -0.5, -0.25, 0.0]
+        )
+    }
+
+    #[test]
+    fn aliasing_square() {
+        let naive = aliasing(generate(
+            Signal::Square,
+            Oscillator::Naive,
+            INCREMENT,
+            LENGTH,
+        ));
+        let blep = aliasing(generate(
+            Signal::Square,
+            Oscillator::PolyBlep,
+            INCREMENT,
+            LENGTH,
+        ));
+        assert!(blep < naive / 10.0, "{} >= {} / 10", blep, naive);
+    }
+
+    #[test]
+    fn aliasing_saw() {
+        let naive = aliasing(generate(Signal::Saw, Oscillator::Naive, INCREMENT, LENGTH));
+        let blep = aliasing(generate(
+            Signal::Saw,
+            Oscillator::PolyBlep,
+            INCREMENT,
+            LENGTH,
+        ));
+        assert!(blep < naive / 10.0, "{} >= {} / 10", blep, naive);
+    }
+
     #[test]
     fn display() {
         assert_eq!("Sine", format!("{}", Signal::Sine));
         assert_eq!("Saw", format!("{}", Signal::Saw));
+        assert_eq!("PolyBlep", format!("{}", Oscillator::PolyBlep));
+    }
+
+    #[test]
+    fn encode() {
+        for oscillator in [Oscillator::Naive, Oscillator::PolyBlep] {
+            assert_eq!(oscillator, Oscillator::try_from(oscillator as u8).unwrap());
+        }
+        assert!(Oscillator::try_from(2).is_err());
     }
 }
@@ -0,0 +1,92 @@
+use crate::constants::SMOOTH;
+
+#[derive(Clone)]
+pub struct Smooth {
+    value: f32,
+    target: f32,
+    delta: f32,
+    steps: usize,
+}
+
+impl Smooth {
+    pub fn new(value: f32) -> Self {
+        Smooth {
+            value,
+            target: value,
+            delta: 0.0,
+            steps: 0,
+        }
+    }
+
+    pub fn set(&mut self, target: f32) {
+        self.target = target;
+        self.delta = (target - self.value) / SMOOTH as f32;
+        self.steps = SMOOTH;
+    }
+
+    pub fn settle(&mut self) {
+        self.value = self.target;
+        self.steps = 0;
+    }
+
+    pub fn active(&self) -> bool {
+        self.steps > 0
+    }
+
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    pub fn next(&mut self) -> f32 {
+        if self.steps > 0 {
+            self.steps -= 1;
+            self.value = if self.steps == 0 {
+                self.target
+            } else {
+                self.value + self.delta
+            };
+        }
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new() {
+        let mut smooth = Smooth::new(2.0);
+        assert!(!smooth.active());
+        assert_eq!(2.0, smooth.next());
+    }
+
+    #[test]
+    fn set() {
+        let mut smooth = Smooth::new(0.0);
+        smooth.set(SMOOTH as f32);
+        assert!(smooth.active());
+        assert_eq!(SMOOTH as f32, smooth.target());
+
+        let values = (0..SMOOTH + 1).map(|_| smooth.next()).collect::<Vec<f32>>();
+        assert_eq!(1.0, values[0]);
+        assert!(values.windows(2).all(|w| w[1] - w[0] <= 1.0));
+        assert_eq!(SMOOTH as f32, values[SMOOTH - 1]);
+        assert_eq!(SMOOTH as f32, values[SMOOTH]);
+        assert!(!smooth.active());
+    }
+
+    #[test]
+    fn settle() {
+        let mut smooth = Smooth::new(0.0);
+        smooth.set(1.0);
+        smooth.next();
+        smooth.settle();
+        assert!(!smooth.active());
+        assert_eq!(1.0, smooth.next());
+    }
+}
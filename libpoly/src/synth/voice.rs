@@ -0,0 +1,462 @@
+use core::f32::consts::TAU;
+use core::fmt;
+
+use super::adsr::{Timing, ADSR};
+use super::algorithm::Algorithm;
+use super::filter::{Filter, Response};
+use super::matrix::{Modulation, Route};
+use super::operator::Operator;
+use super::pan::Pan;
+use super::smooth::Smooth;
+use crate::constants::{ALGORITHMS, LFOS, ROUTES};
+use crate::snapshot::VoiceState;
+use crate::Frame;
+
+#[derive(Clone)]
+pub struct Voice {
+    algorithm: &'static Algorithm,
+    indices: [[f32; 3]; 3],
+    feedback: f32,
+    history: [f32; 2],
+    operators: [Operator; 3],
+    envelopes: [ADSR; 3],
+    filter: Filter,
+    pan: Pan,
+    levels: [Smooth; 3],
+    amplitude: Smooth,
+    velocity: f32,
+    frequency: f32,
+    age: usize,
+}
+
+impl Voice {
+    pub fn new(
+        algorithm: &'static Algorithm,
+        indices: [[f32; 3]; 3],
+        feedback: f32,
+        operators: [Operator; 3],
+        envelopes: [ADSR; 3],
+        filter: Filter,
+        pan: Pan,
+    ) -> Self {
+        Voice {
+            algorithm,
+            indices,
+            feedback,
+            history: [0.0; 2],
+            operators,
+            envelopes,
+            filter,
+            pan,
+            levels: core::array::from_fn(|_| Smooth::new(1.0)),
+            amplitude: Smooth::new(1.0),
+            velocity: 1.0,
+            frequency: 0.0,
+            age: 0,
+        }
+    }
+
+    pub fn start(
+        &mut self,
+        age: usize,
+        length: usize,
+        frequency: f32,
+        velocity: f32,
+        position: f32,
+    ) {
+        let idle = !self.active();
+        for operator in &mut self.operators {
+            operator.settle();
+            operator.set_frequency(frequency);
+        }
+        for envelope in &mut self.envelopes {
+            envelope.start(length);
+        }
+
+        self.filter.start(length, frequency, velocity);
+        if idle {
+            self.filter.settle();
+        }
+        self.pan.set_position(position);
+        self.velocity = velocity;
+        self.frequency = frequency;
+        self.age = age;
+    }
+
+    pub fn stop(&mut self) {
+        for envelope in &mut self.envelopes {
+            envelope.stop();
+        }
+
+        self.filter.stop();
+    }
+
+    pub fn extend(&mut self, age: usize, length: usize) {
+        for envelope in &mut self.envelopes {
+            envelope.extend(length);
+        }
+
+        self.filter.extend(length);
+        self.age = age;
+    }
+
+    pub fn settle(&mut self) {
+        for level in &mut self.levels {
+            level.settle();
+        }
+        self.amplitude.settle();
+    }
+
+    pub fn set_operator(&mut self, index: usize, operator: &Operator) {
+        self.operators[index].set(operator);
+    }
+
+    pub fn set_envelope(
+        &mut self,
+        index: usize,
+        attack: f32,
+        decay: f32,
+        sustain: f32,
+        release: f32,
+    ) {
+        self.envelopes[index].set(attack, decay, sustain, release);
+    }
+
+    pub fn set_curve(&mut self, index: usize, curve: f32, timing: Timing) {
+        self.envelopes[index].set_curve(curve, timing);
+    }
+
+    pub fn set_filter(&mut self, cutoff: f32, q: f32) {
+        self.filter.set(cutoff, q);
+    }
+
+    pub fn set_response(&mut self, response: Response) {
+        self.filter.set_response(response);
+    }
+
+    pub fn modulate(&mut self, routes: &[Route; ROUTES], lfos: &[f32; LFOS]) {
+        let envelopes = self.envelopes.each_ref().map(|envelope| envelope.value());
+        let mut modulation = Modulation::default();
+        for route in routes {
+            route.apply(lfos, &envelopes, &mut modulation);
+        }
+
+        for (operator, shift) in self.operators.iter_mut().zip(modulation.ratios) {
+            operator.set_shift(shift);
+        }
+        for (level, shift) in self.levels.iter_mut().zip(modulation.levels) {
+            level.set((1.0 + shift).max(0.0));
+        }
+        self.filter.set_shift(modulation.cutoff, modulation.q);
+        self.pan.set_shift(modulation.pan);
+        self.amplitude.set((1.0 + modulation.amplitude).max(0.0));
+    }
+
+    pub fn active(&self) -> bool {
+        self.carriers().any(|envelope| envelope.active())
+    }
+
+    pub fn gated(&self) -> bool {
+        self.carriers().any(|envelope| envelope.gated())
+    }
+
+    pub fn level(&self) -> f32 {
+        self.carriers()
+            .map(|envelope| envelope.value())
+            .fold(0.0, f32::max)
+    }
+
+    pub fn envelope(&self, index: usize) -> Option<&ADSR> {
+        self.envelopes.get(index)
+    }
+
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    pub fn age(&self) -> usize {
+        self.age
+    }
+
+    pub fn snapshot(&self) -> VoiceState {
+        VoiceState {
+            algorithm: ALGORITHMS
+                .iter()
+                .position(|algorithm| algorithm.name == self.algorithm.name)
+                .unwrap_or(0),
+            indices: self.indices,
+            feedback: self.feedback,
+            operators: [
+                self.operators[0].snapshot(),
+                self.operators[1].snapshot(),
+                self.operators[2].snapshot(),
+            ],
+            envelopes: [
+                self.envelopes[0].snapshot(),
+                self.envelopes[1].snapshot(),
+                self.envelopes[2].snapshot(),
+            ],
+            filter: self.filter.snapshot(),
+            pan: self.pan.snapshot(),
+        }
+    }
+
+    pub fn render(&mut self, buffer: &mut [f32], out: &mut [Frame], level: f32) {
+        let gain = self.algorithm.gain() * level * self.velocity;
+
+        for sample in buffer.iter_mut() {
+            *sample = self.next() * gain;
+        }
+
+        self.filter.process(buffer);
+        self.pan.render(buffer, out);
+    }
+
+    fn next(&mut self) -> f32 {
+        let mut outputs = [0.0; 3];
+        let mut sample = 0.0;
+
+        for index in (0..3).rev() {
+            let mut modulation = self.indices[index]
+                .iter()
+                .zip(outputs.iter())
+                .fold(0.0, |modulation, (index, output)| {
+                    modulation + index * output
+                });
+            if index == self.algorithm.feedback {
+                modulation += self.feedback * (self.history[0] + self.history[1]) * 0.5;
+            }
+
+            outputs[index] = self.operators[index].next(modulation / TAU)
+                * self.envelopes[index].next()
+                * self.levels[index].next();
+            if self.algorithm.carriers[index] {
+                sample += outputs[index];
+            }
+        }
+
+        self.history = [outputs[self.algorithm.feedback], self.history[0]];
+        sample * self.amplitude.next()
+    }
+
+    fn carriers(&self) -> impl Iterator<Item = &ADSR> {
+        self.envelopes
+            .iter()
+            .zip(self.algorithm.carriers.iter())
+            .filter(|(_, &carrier)| carrier)
+            .map(|(envelope, _)| envelope)
+    }
+}
+
+impl fmt::Display for Voice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[ALGORITHM] {} ({:.2}, {:.2}, {:.2}) [FEEDBACK] {:.2} [FILTER] {} [ADSR] {}, {}, {} [PAN] {} [OPERATORS] {}, {}, {}",
+            self.algorithm,
+            self.indices[0][1],
+            self.indices[0][2],
+            self.indices[1][2],
+            self.feedback,
+            self.filter,
+            self.envelopes[0],
+            self.envelopes[1],
+            self.envelopes[2],
+            self.pan,
+            self.operators[0],
+            self.operators[1],
+            self.operators[2]
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Steal {
+    Oldest,
+    Quietest,
+    SamePitch,
+}
+
+impl Steal {
+    pub fn choose(&self, voices: &[Voice], frequency: f32) -> usize {
+        let oldest = || {
+            (0..voices.len())
+                .min_by_key(|&index| voices[index].age())
+                .unwrap_or(0)
+        };
+
+        match self {
+            Steal::Oldest => oldest(),
+            Steal::Quietest => (0..voices.len())
+                .min_by(|&a, &b| voices[a].level().total_cmp(&voices[b].level()))
+                .unwrap_or(0),
+            Steal::SamePitch => voices
+                .iter()
+                .position(|voice| voice.frequency() == frequency)
+                .unwrap_or_else(oldest),
+        }
+    }
+}
+
+impl fmt::Display for Steal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Steal::Oldest => "Oldest",
+                Steal::Quietest => "Quietest",
+                Steal::SamePitch => "SamePitch",
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::*;
+    use crate::config::PolyConfig;
+    use crate::constants::SMOOTH;
+    use crate::synth::{Destination, Oscillator, Signal, Source};
+
+    fn voice(algorithm: &'static Algorithm, indices: [[f32; 3]; 3], feedback: f32) -> Voice {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let config = PolyConfig::default();
+        let mut voice = Voice::new(
+            algorithm,
+            indices,
+            feedback,
+            [
+                Operator::new(64.0, 1.0, Signal::Sine, Oscillator::Naive),
+                Operator::new(64.0, 2.0, Signal::Sine, Oscillator::Naive),
+                Operator::new(64.0, 0.5, Signal::Sine, Oscillator::Naive),
+            ],
+            [
+                ADSR::random(&mut rng, 64.0, &config),
+                ADSR::random(&mut rng, 64.0, &config),
+                ADSR::random(&mut rng, 64.0, &config),
+            ],
+            Filter::random(&mut rng, 64.0, &config).unwrap(),
+            Pan::new(64.0, 0.0, 0.0),
+        );
+        voice.start(1, 64, 3.0, 1.0, 0.0);
+        voice
+    }
+
+    fn carrier(voice: &Voice) -> Vec<f32> {
+        let mut operator = voice.operators[0].clone();
+        let mut envelope = voice.envelopes[0].clone();
+        (0..32)
+            .map(|_| operator.next(0.0) * envelope.next())
+            .collect()
+    }
+
+    #[test]
+    fn unmodulated() {
+        let mut voice = voice(&ALGORITHMS[0], [[0.0; 3]; 3], 0.8);
+        let expected = carrier(&voice);
+        assert_eq!(
+            expected,
+            (0..32).map(|_| voice.next()).collect::<Vec<f32>>()
+        );
+    }
+
+    #[test]
+    fn modulated() {
+        let mut indices = [[0.0; 3]; 3];
+        indices[0][1] = 2.0;
+
+        let mut voice = voice(&ALGORITHMS[0], indices, 0.0);
+        let expected = carrier(&voice);
+        assert_ne!(
+            expected,
+            (0..32).map(|_| voice.next()).collect::<Vec<f32>>()
+        );
+    }
+
+    #[test]
+    fn feedback() {
+        let mut voice = voice(&ALGORITHMS[4], [[0.0; 3]; 3], 0.8);
+        let mut unmodulated = voice.clone();
+        unmodulated.feedback = 0.0;
+
+        assert_eq!(voice.next(), unmodulated.next());
+        assert_ne!(
+            (0..32).map(|_| unmodulated.next()).collect::<Vec<f32>>(),
+            (0..32).map(|_| voice.next()).collect::<Vec<f32>>()
+        );
+    }
+
+    #[test]
+    fn active() {
+        let mut voice = voice(&ALGORITHMS[0], [[0.0; 3]; 3], 0.0);
+        assert!(voice.active());
+
+        voice.envelopes[0] = ADSR::default();
+        assert!(!voice.active());
+        assert_eq!(0.0, voice.level());
+    }
+
+    #[test]
+    fn modulate() {
+        let routes = [
+            Route::new(Source::Lfo(0), Destination::Level(0), 1.0),
+            Route::new(Source::Lfo(1), Destination::Amplitude, 0.5),
+            Route::new(Source::Envelope(0), Destination::Ratio(0), 1.0),
+            Route::new(Source::Lfo(0), Destination::Level(1), -2.0),
+        ];
+        let mut voice = voice(&ALGORITHMS[0], [[0.0; 3]; 3], 0.0);
+        let expected = carrier(&voice)
+            .iter()
+            .map(|sample| sample * 0.5)
+            .collect::<Vec<f32>>();
+
+        let mut settled = voice.clone();
+        settled.modulate(&routes, &[-0.5, 0.0]);
+        settled.settle();
+        assert_eq!(
+            expected,
+            (0..32).map(|_| settled.next()).collect::<Vec<f32>>()
+        );
+
+        voice.modulate(&routes, &[-0.5, 0.0]);
+        assert_eq!([0.5, 2.0, 1.0], voice.levels.each_ref().map(Smooth::target));
+        assert_eq!(1.0, voice.amplitude.target());
+        let ramped = (0..32).map(|_| voice.next()).collect::<Vec<f32>>();
+        for (index, (ramped, expected)) in ramped.iter().zip(&expected).enumerate() {
+            let level = 1.0 - 0.5 * (index + 1) as f32 / SMOOTH as f32;
+            assert!((ramped - expected * 2.0 * level).abs() < 1e-6);
+        }
+        assert_eq!(expected[31], ramped[31]);
+
+        voice.modulate(&routes, &[0.0, -4.0]);
+        assert_eq!([1.0; 3], voice.levels.each_ref().map(Smooth::target));
+        assert_eq!(0.0, voice.amplitude.target());
+
+        let faded = (0..64).map(|_| voice.next()).collect::<Vec<f32>>();
+        assert!(faded[..SMOOTH - 1].iter().any(|&sample| sample != 0.0));
+        assert!(faded[SMOOTH - 1..].iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn velocity() {
+        let mut loud = voice(&ALGORITHMS[0], [[0.0; 3]; 3], 0.0);
+        loud.set_response(Response::Lowpass);
+        loud.filter.set_modulation(ADSR::default(), 0.0, 0.0, 0.0);
+        let mut soft = loud.clone();
+        loud.start(1, 64, 3.0, 1.0, 0.0);
+        soft.start(1, 64, 3.0, 0.5, 0.0);
+
+        let mut expected = [[0.0; 2]; 32];
+        let mut actual = [[0.0; 2]; 32];
+        loud.render(&mut [0.0; 32], &mut expected, 1.0);
+        soft.render(&mut [0.0; 32], &mut actual, 1.0);
+        assert!(expected.iter().any(|frame| *frame != [0.0; 2]));
+        assert_eq!(
+            expected.map(|frame| frame.map(|sample| sample * 0.5)),
+            actual
+        );
+    }
+}
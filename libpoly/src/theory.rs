@@ -0,0 +1,318 @@
+use core::fmt;
+
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng};
+
+use crate::constants::{MODES, ROOT};
+use crate::error::Error;
+use crate::snapshot::ScaleState;
+
+const NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+const NUMERALS: [&str; 7] = ["I", "II", "III", "IV", "V", "VI", "VII"];
+
+pub struct Mode {
+    pub name: &'static str,
+    pub intervals: &'static [i32],
+}
+
+#[derive(Clone, Copy)]
+pub struct Scale {
+    pub root: i32,
+    pub mode: &'static Mode,
+}
+
+impl Scale {
+    pub fn new(root: i32, mode: &'static Mode) -> Self {
+        Scale { root, mode }
+    }
+
+    pub fn random(rng: &mut SmallRng) -> Result<Self, Error> {
+        let root = rng.gen_range(0..12);
+        let mode = MODES.choose(rng).ok_or(Error::Rng)?;
+
+        Ok(Self::new(root, mode))
+    }
+
+    pub fn related(&self, rng: &mut SmallRng) -> Self {
+        let fifth = if rng.gen_bool(0.5) { 7 } else { 5 };
+        self.transpose(fifth)
+    }
+
+    pub fn transpose(&self, semitones: i32) -> Self {
+        Self::new((self.root + semitones).rem_euclid(12), self.mode)
+    }
+
+    pub fn len(&self) -> usize {
+        self.mode.intervals.len()
+    }
+
+    pub fn chord(&self, chord: usize) -> usize {
+        (2 * chord * self.len() + 7) / 14
+    }
+
+    pub fn triad(&self, root: usize, tone: usize) -> usize {
+        let span = (self.len() / 3).max(1);
+        (0..tone).fold(root, |degree, _| {
+            let mut next = degree + span;
+            while self.interval(next) - self.interval(degree) < 3 {
+                next += 1;
+            }
+            next
+        })
+    }
+
+    fn interval(&self, degree: usize) -> i32 {
+        let length = self.len();
+        12 * (degree / length) as i32 + self.mode.intervals[degree % length]
+    }
+
+    pub fn note(&self, degree: usize, octave: i32) -> i32 {
+        let length = self.len();
+        ROOT + self.root
+            + 12 * (octave + (degree / length) as i32)
+            + self.mode.intervals[degree % length]
+    }
+
+    pub fn snapshot(&self) -> ScaleState {
+        ScaleState {
+            root: self.root,
+            mode: MODES
+                .iter()
+                .position(|mode| mode.name == self.mode.name)
+                .unwrap_or(0),
+        }
+    }
+}
+
+impl TryFrom<ScaleState> for Scale {
+    type Error = Error;
+
+    fn try_from(state: ScaleState) -> Result<Self, Error> {
+        if !(0..12).contains(&state.root) {
+            return Err(Error::Snapshot);
+        }
+        let mode = MODES.get(state.mode).ok_or(Error::Snapshot)?;
+        Ok(Self::new(state.root, mode))
+    }
+}
+
+pub struct Progression {
+    pub name: &'static str,
+    pub chords: &'static [usize],
+}
+
+pub struct Numerals<'a> {
+    progression: &'a Progression,
+    scale: &'a Scale,
+}
+
+impl Progression {
+    pub fn chord(&self, index: usize) -> usize {
+        self.chords[index % self.chords.len()]
+    }
+
+    pub fn numerals<'a>(&'a self, scale: &'a Scale) -> Numerals<'a> {
+        Numerals {
+            progression: self,
+            scale,
+        }
+    }
+}
+
+impl fmt::Display for Scale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            NAMES[self.root.rem_euclid(12) as usize],
+            self.mode.name
+        )
+    }
+}
+
+impl fmt::Display for Progression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (", self.name)?;
+        for (i, chord) in self.chords.iter().enumerate() {
+            write!(f, "{}", NUMERALS[chord % NUMERALS.len()])?;
+            if i != self.chords.len() - 1 {
+                write!(f, "-")?;
+            }
+        }
+        write!(f, ")")
+    }
+}
+
+impl fmt::Display for Numerals<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let chords = self.progression.chords;
+        write!(f, "{} (", self.progression.name)?;
+        for (i, &chord) in chords.iter().enumerate() {
+            write!(f, "{}", NUMERALS[self.scale.chord(chord) % NUMERALS.len()])?;
+            if i != chords.len() - 1 {
+                write!(f, "-")?;
+            }
+        }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::constants::PROGRESSIONS;
+
+    #[test]
+    fn random() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let scale = Scale::random(&mut rng).unwrap();
+        assert_eq!(5, scale.root);
+        assert_eq!("Aeolian", scale.mode.name);
+    }
+
+    #[test]
+    fn related() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let scale = Scale::new(9, &MODES[5]);
+        for _ in 0..10 {
+            let related = scale.related(&mut rng);
+            assert!([2, 4].contains(&related.root));
+            assert_eq!("Aeolian", related.mode.name);
+        }
+    }
+
+    #[test]
+    fn transpose() {
+        let scale = Scale::new(9, &MODES[5]);
+        assert_eq!(0, scale.transpose(3).root);
+        assert_eq!(6, scale.transpose(-3).root);
+        assert_eq!(9, scale.transpose(24).root);
+    }
+
+    #[test]
+    fn modes() {
+        for mode in &MODES {
+            assert_eq!(0, mode.intervals[0], "{}", mode.name);
+            assert!(mode.intervals.windows(2).all(|w| w[0] < w[1]));
+            assert!(mode.intervals.iter().all(|&interval| interval < 12));
+        }
+    }
+
+    #[test]
+    fn note() {
+        let major = Scale::new(0, &MODES[0]);
+        assert_eq!(
+            vec![60, 62, 64, 65, 67, 69, 71, 72, 74],
+            (0..9)
+                .map(|degree| major.note(degree, 0))
+                .collect::<Vec<i32>>()
+        );
+        assert_eq!(36, major.note(0, -2));
+        assert_eq!(83, major.note(6, 1));
+
+        let minor = Scale::new(9, &MODES[5]);
+        assert_eq!(69, minor.note(0, 0));
+        assert_eq!(72, minor.note(2, 0));
+    }
+
+    #[test]
+    fn snapshot() {
+        let scale = Scale::new(9, &MODES[5]);
+        let state = scale.snapshot();
+        assert_eq!(ScaleState { root: 9, mode: 5 }, state);
+        assert_eq!("A Aeolian", format!("{}", Scale::try_from(state).unwrap()));
+
+        assert!(Scale::try_from(ScaleState { root: 12, mode: 0 }).is_err());
+        assert!(Scale::try_from(ScaleState { root: 0, mode: 11 }).is_err());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!("C Ionian", format!("{}", Scale::new(0, &MODES[0])));
+        assert_eq!("F# Dorian", format!("{}", Scale::new(6, &MODES[1])));
+    }
+
+    #[test]
+    fn progressions() {
+        for progression in &PROGRESSIONS {
+            assert!(!progression.chords.is_empty(), "{}", progression);
+            assert!(progression.chords.iter().all(|&chord| chord < 7));
+        }
+
+        let progression = &PROGRESSIONS[0];
+        assert_eq!(0, progression.chord(0));
+        assert_eq!(4, progression.chord(2));
+        assert_eq!(0, progression.chord(4));
+        assert_eq!("Functional (I-IV-V-I)", format!("{}", progression));
+    }
+
+    #[test]
+    fn triads() {
+        let major = Scale::new(0, &MODES[0]);
+        assert_eq!(
+            vec![67, 71, 74],
+            (0..3)
+                .map(|tone| major.note(major.triad(4, tone), 0))
+                .collect::<Vec<i32>>()
+        );
+
+        let minor = Scale::new(9, &MODES[7]);
+        assert_eq!(
+            vec![77, 81, 84],
+            (0..3)
+                .map(|tone| minor.note(minor.triad(5, tone), 0))
+                .collect::<Vec<i32>>()
+        );
+
+        for mode in &MODES {
+            let scale = Scale::new(0, mode);
+            for root in 0..mode.intervals.len() {
+                let notes = (0..3)
+                    .map(|tone| scale.note(scale.triad(root, tone), 0))
+                    .collect::<Vec<i32>>();
+                assert!(
+                    notes.windows(2).all(|w| (3..=5).contains(&(w[1] - w[0]))),
+                    "{} {:?}",
+                    mode.name,
+                    notes
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn pentatonic() {
+        let pentatonic = Scale::new(0, &MODES[8]);
+        let progression = &PROGRESSIONS[0];
+        assert_eq!(
+            vec![0, 2, 3, 0],
+            progression
+                .chords
+                .iter()
+                .map(|&chord| pentatonic.chord(chord))
+                .collect::<Vec<usize>>()
+        );
+        assert_eq!(
+            "Functional (I-III-IV-I)",
+            format!("{}", progression.numerals(&pentatonic))
+        );
+        assert_eq!(
+            vec![67, 72, 76],
+            (0..3)
+                .map(|tone| pentatonic.note(pentatonic.triad(3, tone), 0))
+                .collect::<Vec<i32>>()
+        );
+
+        let major = Scale::new(0, &MODES[0]);
+        assert_eq!(
+            format!("{}", progression),
+            format!("{}", progression.numerals(&major))
+        );
+        assert!((0..7).all(|chord| major.chord(chord) == chord));
+    }
+}
@@ -0,0 +1,155 @@
+use core::fmt;
+use core::ops::RangeInclusive;
+
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use crate::config::PolyConfig;
+use crate::constants::VELOCITY;
+use crate::error::Error;
+use crate::score::Score;
+use crate::sequence::Sequence;
+use crate::snapshot::TrackState;
+use crate::synth::{Oscillator, Response, Signal, Steal, Synth, Timing};
+use crate::Frame;
+
+pub struct Part {
+    pub octaves: RangeInclusive<i32>,
+    pub stretch: usize,
+    pub level: f32,
+}
+
+pub struct Track {
+    part: &'static Part,
+    rng: SmallRng,
+    sequence: Sequence,
+    synth: Synth,
+}
+
+impl Track {
+    pub fn new(
+        rng: &mut SmallRng,
+        score: &Score,
+        sample_rate: f32,
+        part: &'static Part,
+        config: &PolyConfig,
+    ) -> Result<Self, Error> {
+        let mut rng = SmallRng::seed_from_u64(rng.gen());
+
+        let sequence = Sequence::new(&mut rng, score, part, config)?;
+        let synth = Synth::new(&mut rng, sample_rate, part.level, config)?;
+
+        Ok(Track {
+            part,
+            rng,
+            sequence,
+            synth,
+        })
+    }
+
+    pub fn set_polyphony(&mut self, polyphony: usize, steal: Steal) -> Result<(), Error> {
+        self.synth.set_polyphony(polyphony, steal)
+    }
+
+    pub fn set_legato(&mut self, legato: bool) {
+        self.synth.set_legato(legato);
+    }
+
+    pub fn train(&mut self, degrees: &[usize]) {
+        self.sequence.train(degrees);
+    }
+
+    pub fn set_locked(&mut self, locked: bool) {
+        self.sequence.set_locked(locked);
+        self.synth.set_locked(locked);
+    }
+
+    pub fn set_rate(&mut self, rate: f32) {
+        self.sequence.set_rate(rate);
+        self.synth.set_rate(rate);
+    }
+
+    pub fn set_filter(&mut self, cutoff: f32, q: f32) -> Result<(), Error> {
+        self.synth.set_filter(cutoff, q)
+    }
+
+    pub fn set_response(&mut self, response: Response) {
+        self.synth.set_response(response);
+    }
+
+    pub fn set_envelope(
+        &mut self,
+        operator: usize,
+        attack: f32,
+        decay: f32,
+        sustain: f32,
+        release: f32,
+    ) -> Result<(), Error> {
+        self.synth
+            .set_envelope(operator, attack, decay, sustain, release)
+    }
+
+    pub fn set_curve(&mut self, operator: usize, curve: f32, timing: Timing) -> Result<(), Error> {
+        self.synth.set_curve(operator, curve, timing)
+    }
+
+    pub fn set_operator(
+        &mut self,
+        operator: usize,
+        signal: Signal,
+        oscillator: Oscillator,
+        ratio: f32,
+    ) -> Result<(), Error> {
+        self.synth.set_operator(operator, signal, oscillator, ratio)
+    }
+
+    pub fn snapshot(&mut self) -> TrackState {
+        let seed = self.rng.gen();
+        self.rng = SmallRng::seed_from_u64(seed);
+
+        TrackState {
+            seed,
+            sequence: self.sequence.snapshot(),
+            synth: self.synth.snapshot(),
+        }
+    }
+
+    pub fn restore(&mut self, state: &TrackState) -> Result<(), Error> {
+        self.sequence.restore(&state.sequence)?;
+        self.synth.restore(&state.synth)?;
+        self.rng = SmallRng::seed_from_u64(state.seed);
+        Ok(())
+    }
+
+    pub fn level(&self) -> f32 {
+        self.part.level
+    }
+
+    pub fn next(&mut self, score: &Score) -> Option<(usize, f32, f32)> {
+        let (length, frequency) = self.sequence.next(&mut self.rng, score)?;
+        let velocity = self.rng.gen_range(VELOCITY);
+        self.synth.play(&mut self.rng, length, frequency, velocity);
+        Some((length, frequency, velocity))
+    }
+
+    pub fn locate(&mut self, score: &Score, tick: usize) {
+        self.sequence.locate(score, tick);
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.sequence.remaining()
+    }
+
+    pub fn skip(&mut self, samples: usize) {
+        self.sequence.skip(samples);
+    }
+
+    pub fn render(&mut self, out: &mut [Frame], tempo: f32) {
+        self.synth.render(out, tempo);
+    }
+}
+
+impl fmt::Display for Track {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.sequence, self.synth)
+    }
+}
@@ -0,0 +1,191 @@
+use core::fmt;
+
+use crate::constants::{FOLLOW, FOLLOW_TEMPOS, PPQ, PULSES};
+use crate::error::Error;
+
+pub const TICKS: usize = PPQ / PULSES;
+pub const STEP: usize = PPQ / 4;
+
+const CLOCK: u8 = 0xF8;
+const START: u8 = 0xFA;
+const CONTINUE: u8 = 0xFB;
+const STOP: u8 = 0xFC;
+const POSITION: u8 = 0xF2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Transport {
+    Clock,
+    Start,
+    Continue,
+    Stop,
+    Position(usize),
+}
+
+impl Transport {
+    pub fn parse(message: &[u8]) -> Option<Self> {
+        match *message {
+            [CLOCK] => Some(Transport::Clock),
+            [START] => Some(Transport::Start),
+            [CONTINUE] => Some(Transport::Continue),
+            [STOP] => Some(Transport::Stop),
+            [POSITION, lsb, msb] if lsb < 0x80 && msb < 0x80 => {
+                Some(Transport::Position((msb as usize) << 7 | lsb as usize))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self, out: &mut [u8]) -> Result<usize, Error> {
+        let (bytes, length) = match *self {
+            Transport::Clock => ([CLOCK, 0, 0], 1),
+            Transport::Start => ([START, 0, 0], 1),
+            Transport::Continue => ([CONTINUE, 0, 0], 1),
+            Transport::Stop => ([STOP, 0, 0], 1),
+            Transport::Position(position) => {
+                let position = position.min(0x3FFF);
+                (
+                    [POSITION, (position & 0x7F) as u8, (position >> 7) as u8],
+                    3,
+                )
+            }
+        };
+        out.get_mut(..length)
+            .ok_or(Error::Array)?
+            .copy_from_slice(&bytes[..length]);
+        Ok(length)
+    }
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Transport::Clock => write!(f, "Clock"),
+            Transport::Start => write!(f, "Start"),
+            Transport::Continue => write!(f, "Continue"),
+            Transport::Stop => write!(f, "Stop"),
+            Transport::Position(position) => write!(f, "Position {}", position),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Role {
+    Internal,
+    Follow,
+    Lead,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Role::Internal => write!(f, "Internal"),
+            Role::Follow => write!(f, "Follow"),
+            Role::Lead => write!(f, "Lead"),
+        }
+    }
+}
+
+pub struct Follower {
+    pub tick: usize,
+    pub armed: bool,
+    sample_rate: f64,
+    frames: [u64; PULSES],
+    count: usize,
+    bpm: Option<f64>,
+}
+
+impl Follower {
+    pub fn new(sample_rate: f32) -> Self {
+        Follower {
+            tick: 0,
+            armed: false,
+            sample_rate: sample_rate as f64,
+            frames: [0; PULSES],
+            count: 0,
+            bpm: None,
+        }
+    }
+
+    pub fn clock(&mut self, frame: u64) -> Option<f32> {
+        let slot = self.count % PULSES;
+        if self.count >= PULSES {
+            let beat = frame.saturating_sub(self.frames[slot]).max(1) as f64;
+            let bpm = 60.0 * self.sample_rate / beat;
+            self.bpm = Some(match self.bpm {
+                Some(previous) => previous + (bpm - previous) * FOLLOW,
+                None => bpm,
+            });
+        }
+        self.frames[slot] = frame;
+        self.count += 1;
+
+        self.bpm
+            .map(|bpm| (bpm as f32).clamp(*FOLLOW_TEMPOS.start(), *FOLLOW_TEMPOS.end()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Some(Transport::Clock), Transport::parse(&[0xF8]));
+        assert_eq!(Some(Transport::Start), Transport::parse(&[0xFA]));
+        assert_eq!(Some(Transport::Continue), Transport::parse(&[0xFB]));
+        assert_eq!(Some(Transport::Stop), Transport::parse(&[0xFC]));
+        assert_eq!(
+            Some(Transport::Position(300)),
+            Transport::parse(&[0xF2, 0x2C, 0x02])
+        );
+        assert_eq!(None, Transport::parse(&[0xF2, 0x80, 0x00]));
+        assert_eq!(None, Transport::parse(&[0x90, 60, 100]));
+    }
+
+    #[test]
+    fn encode() {
+        let mut out = [0; 3];
+        for transport in [
+            Transport::Clock,
+            Transport::Start,
+            Transport::Continue,
+            Transport::Stop,
+            Transport::Position(300),
+        ] {
+            let length = transport.encode(&mut out).unwrap();
+            assert_eq!(Some(transport), Transport::parse(&out[..length]));
+        }
+
+        assert_eq!(3, Transport::Position(100_000).encode(&mut out).unwrap());
+        assert_eq!([0xF2, 0x7F, 0x7F], out);
+        assert!(Transport::Position(0).encode(&mut out[..2]).is_err());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!("Position 12", format!("{}", Transport::Position(12)));
+        assert_eq!("Follow", format!("{}", Role::Follow));
+    }
+
+    #[test]
+    fn follower() {
+        let mut follower = Follower::new(48_000.0);
+        let interval = 1_000;
+        let mut bpm = None;
+        for pulse in 0..PULSES as u64 {
+            bpm = follower.clock(pulse * interval);
+        }
+        assert_eq!(None, bpm);
+
+        for pulse in PULSES as u64..PULSES as u64 * 4 {
+            bpm = follower.clock(pulse * interval);
+        }
+        assert_eq!(Some(120.0), bpm);
+
+        let mut follower = Follower::new(48_000.0);
+        for pulse in 0..PULSES as u64 * 2 {
+            bpm = follower.clock(pulse);
+        }
+        assert_eq!(Some(*FOLLOW_TEMPOS.end()), bpm);
+    }
+}
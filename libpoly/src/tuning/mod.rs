@@ -0,0 +1,158 @@
+mod scala;
+
+use core::fmt;
+
+use libm::{exp2f, roundf};
+
+use crate::constants::JUST;
+use crate::theory::{Mode, Scale};
+pub use scala::Scala;
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone)]
+pub enum Tuning {
+    Equal(u32),
+    Just,
+    Scala(Scala),
+}
+
+impl Tuning {
+    pub fn frequency(&self, scale: &Scale, degree: usize, octave: i32) -> Option<f32> {
+        let root = scale.note(0, 0);
+        let note = scale.note(degree, octave);
+
+        match self {
+            Tuning::Equal(divisions) => {
+                let length = scale.len();
+                let divisions = (*divisions).max(length as u32);
+                let octave = octave + (degree / length) as i32;
+                let step = step(scale.mode, divisions, degree % length) as f32;
+                Some(equal(root) * exp2f(octave as f32 + step / divisions as f32))
+            }
+            Tuning::Just => {
+                let interval = note - root;
+                Some(
+                    equal(root)
+                        * exp2f(interval.div_euclid(12) as f32)
+                        * JUST[interval.rem_euclid(12) as usize],
+                )
+            }
+            Tuning::Scala(scala) => scala.frequency(note),
+        }
+    }
+}
+
+fn step(mode: &Mode, divisions: u32, degree: usize) -> u32 {
+    let length = mode.intervals.len();
+    (1..=degree).fold(0, |previous, degree| {
+        let position = mode.intervals[degree] as f32 * divisions as f32 / 12.0;
+        (roundf(position) as u32).clamp(previous + 1, divisions - (length - degree) as u32)
+    })
+}
+
+fn equal(note: i32) -> f32 {
+    440.0 * exp2f((note - 69) as f32 / 12.0)
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Tuning::Equal(12)
+    }
+}
+
+impl fmt::Display for Tuning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tuning::Equal(divisions) => write!(f, "{}-EDO", divisions),
+            Tuning::Just => write!(f, "Just"),
+            Tuning::Scala(scala) => write!(f, "{}", scala),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MODES;
+
+    fn steps(mode: &Mode, divisions: u32) -> Vec<u32> {
+        (0..mode.intervals.len())
+            .map(|degree| step(mode, divisions, degree))
+            .collect()
+    }
+
+    #[test]
+    fn equal() {
+        let tuning = Tuning::default();
+        let minor = Scale::new(9, &MODES[5]);
+        assert_eq!(Some(440.0), tuning.frequency(&minor, 0, 0));
+        assert_eq!(Some(880.0), tuning.frequency(&minor, 7, 0));
+        assert_eq!(Some(220.0), tuning.frequency(&minor, 0, -1));
+        assert_eq!(Some(261.62555), tuning.frequency(&minor.transpose(3), 0, 0));
+
+        for mode in &MODES {
+            let intervals = mode.intervals.iter().map(|&interval| interval as u32);
+            assert_eq!(
+                intervals.collect::<Vec<u32>>(),
+                steps(mode, 12),
+                "{}",
+                mode.name
+            );
+        }
+    }
+
+    #[test]
+    fn edo() {
+        let major = Scale::new(9, &MODES[0]);
+        assert_eq!(vec![0, 3, 6, 8, 11, 14, 17], steps(major.mode, 19));
+        assert_eq!(vec![0, 4, 8, 10, 14, 18, 22], steps(major.mode, 24));
+        assert_eq!(vec![0, 1, 2, 3, 4, 5, 6], steps(major.mode, 7));
+        assert_eq!(vec![0, 1, 3, 4, 5, 6, 7], steps(major.mode, 8));
+
+        let minor = Scale::new(9, &MODES[5]);
+        assert_eq!(vec![0, 3, 5, 8, 11, 13, 16], steps(minor.mode, 19));
+
+        let tuning = Tuning::Equal(24);
+        assert_eq!(Some(440.0), tuning.frequency(&major, 0, 0));
+        assert_eq!(Some(880.0), tuning.frequency(&major, 7, 0));
+        assert_eq!(
+            Some(440.0 * exp2f(8.0 / 24.0)),
+            tuning.frequency(&major, 2, 0)
+        );
+        assert_eq!(
+            Tuning::default().frequency(&major, 2, 0),
+            tuning.frequency(&major, 2, 0)
+        );
+        assert_ne!(
+            Tuning::Equal(19).frequency(&major, 2, 0),
+            tuning.frequency(&major, 2, 0)
+        );
+
+        for mode in &MODES {
+            for divisions in 1..=53 {
+                let scale = Scale::new(0, mode);
+                let tuning = Tuning::Equal(divisions);
+                let frequencies = (0..=2 * mode.intervals.len())
+                    .map(|degree| tuning.frequency(&scale, degree, 0).unwrap())
+                    .collect::<Vec<f32>>();
+                assert!(frequencies.windows(2).all(|w| w[0] < w[1]), "{}", mode.name);
+            }
+        }
+    }
+
+    #[test]
+    fn just() {
+        let tuning = Tuning::Just;
+        let major = Scale::new(9, &MODES[0]);
+        assert_eq!(Some(440.0), tuning.frequency(&major, 0, 0));
+        assert_eq!(Some(660.0), tuning.frequency(&major, 4, 0));
+        assert_eq!(Some(550.0), tuning.frequency(&major, 2, 0));
+        assert_eq!(Some(330.0), tuning.frequency(&major, 4, -1));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!("12-EDO", format!("{}", Tuning::default()));
+        assert_eq!("Just", format!("{}", Tuning::Just));
+    }
+}
@@ -0,0 +1,252 @@
+use core::fmt;
+
+use libm::{exp2f, log2f};
+
+use crate::constants::SCALA;
+use crate::error::Error;
+
+#[derive(Clone)]
+pub struct Scala {
+    pitches: [f32; SCALA],
+    length: usize,
+    mapping: [Option<usize>; SCALA],
+    size: usize,
+    middle: i32,
+    reference: i32,
+    frequency: f32,
+    octave: usize,
+}
+
+impl Scala {
+    pub fn parse(scl: &str, kbm: Option<&str>) -> Result<Self, Error> {
+        let mut lines = entries(scl);
+
+        lines.next().ok_or(Error::Scala)?;
+        let length = integer(lines.next())?;
+        if length == 0 || length >= SCALA {
+            return Err(Error::Scala);
+        }
+
+        let mut pitches = [0.0; SCALA];
+        for pitch in &mut pitches[1..=length] {
+            *pitch = cents(lines.next())?;
+        }
+
+        let mut scala = Scala {
+            pitches,
+            length,
+            mapping: [None; SCALA],
+            size: 0,
+            middle: 60,
+            reference: 60,
+            frequency: 261.62558,
+            octave: length,
+        };
+
+        if let Some(kbm) = kbm {
+            scala.map(kbm)?;
+        }
+
+        Ok(scala)
+    }
+
+    fn map(&mut self, kbm: &str) -> Result<(), Error> {
+        let mut lines = entries(kbm);
+
+        let size = integer(lines.next())?;
+        if size >= SCALA {
+            return Err(Error::Scala);
+        }
+        integer(lines.next())?;
+        integer(lines.next())?;
+        let middle = integer(lines.next())?;
+        let reference = integer(lines.next())?;
+        let frequency = token(lines.next())?
+            .parse::<f32>()
+            .map_err(|_| Error::Scala)?;
+        let octave = integer(lines.next())?;
+
+        for mapping in &mut self.mapping[..size] {
+            *mapping = match lines.next() {
+                Some(line) if line.starts_with('x') => None,
+                line => Some(integer(line)?),
+            };
+        }
+
+        self.size = size;
+        self.middle = middle as i32;
+        self.reference = reference as i32;
+        self.frequency = frequency;
+        self.octave = if size == 0 { self.length } else { octave };
+
+        self.degree(self.reference).ok_or(Error::Scala)?;
+        Ok(())
+    }
+
+    pub fn frequency(&self, note: i32) -> Option<f32> {
+        let cents = self.cents(self.degree(note)?) - self.cents(self.degree(self.reference)?);
+        Some(self.frequency * exp2f(cents / 1200.0))
+    }
+
+    fn degree(&self, note: i32) -> Option<i32> {
+        let offset = note - self.middle;
+        if self.size == 0 {
+            return Some(offset);
+        }
+
+        let size = self.size as i32;
+        let degree = self.mapping[offset.rem_euclid(size) as usize]?;
+        Some(offset.div_euclid(size) * self.octave as i32 + degree as i32)
+    }
+
+    fn cents(&self, degree: i32) -> f32 {
+        let length = self.length as i32;
+        degree.div_euclid(length) as f32 * self.pitches[self.length]
+            + self.pitches[degree.rem_euclid(length) as usize]
+    }
+}
+
+fn entries(text: &str) -> impl Iterator<Item = &str> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('!'))
+}
+
+fn token(line: Option<&str>) -> Result<&str, Error> {
+    line.and_then(|line| line.split_whitespace().next())
+        .ok_or(Error::Scala)
+}
+
+fn integer(line: Option<&str>) -> Result<usize, Error> {
+    token(line)?.parse::<usize>().map_err(|_| Error::Scala)
+}
+
+fn cents(line: Option<&str>) -> Result<f32, Error> {
+    let token = token(line)?;
+    if token.contains('.') {
+        return token.parse::<f32>().map_err(|_| Error::Scala);
+    }
+
+    let (numerator, denominator) = token.split_once('/').unwrap_or((token, "1"));
+    let numerator = numerator.parse::<u32>().map_err(|_| Error::Scala)?;
+    let denominator = denominator.parse::<u32>().map_err(|_| Error::Scala)?;
+    if numerator == 0 || denominator == 0 {
+        return Err(Error::Scala);
+    }
+
+    Ok(1200.0 * log2f(numerator as f32 / denominator as f32))
+}
+
+impl fmt::Display for Scala {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Scala ({} notes, {:.2} cents)",
+            self.length, self.pitches[self.length]
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EQUAL: &str = "! 12-tet.scl
+!
+12 tone equal temperament
+ 12
+!
+ 100.0
+ 200.
+ 300.0
+ 400.0
+ 500.0
+ 600.0
+ 700.0
+ 800.0
+ 900.0
+ 1000.0
+ 1100.0
+ 2/1
+";
+
+    const PENTATONIC: &str = "Just pentatonic
+5
+9/8
+5/4 major third
+3/2
+5/3
+2
+";
+
+    const KBM: &str = "! every white key
+7
+0
+127
+60
+69
+440.0
+5
+! mapping
+0
+x
+1
+x
+2
+3
+x
+4
+x
+x
+x
+";
+
+    #[test]
+    fn parse() {
+        let scala = Scala::parse(EQUAL, None).unwrap();
+        assert_eq!(12, scala.length);
+        assert_eq!(1200.0, scala.pitches[12]);
+        assert_eq!(200.0, scala.pitches[2]);
+        assert_eq!("Scala (12 notes, 1200.00 cents)", format!("{}", scala));
+    }
+
+    #[test]
+    fn equal() {
+        let scala = Scala::parse(EQUAL, None).unwrap();
+        assert_eq!(Some(261.62558), scala.frequency(60));
+        assert!((scala.frequency(69).unwrap() - 440.0).abs() < 1e-3);
+        assert!((scala.frequency(48).unwrap() - 130.81279).abs() < 1e-3);
+    }
+
+    #[test]
+    fn ratios() {
+        let scala = Scala::parse(PENTATONIC, None).unwrap();
+        let ratio = |note| scala.frequency(note).unwrap() / scala.frequency(60).unwrap();
+        assert_eq!(5, scala.length);
+        assert!((ratio(62) - 1.25).abs() < 1e-5);
+        assert!((ratio(65) - 2.0).abs() < 1e-5);
+        assert!((ratio(58) - 0.75).abs() < 1e-5);
+    }
+
+    #[test]
+    fn mapping() {
+        let scala = Scala::parse(PENTATONIC, Some(KBM)).unwrap();
+        assert_eq!(Some(440.0), scala.frequency(69));
+        assert!((scala.frequency(60).unwrap() - 440.0 / 2.25).abs() < 1e-3);
+        assert!((scala.frequency(72).unwrap() - 440.0 / 2.25 * 3.0).abs() < 1e-3);
+        assert_eq!(None, scala.frequency(61));
+        assert_eq!(None, scala.frequency(66));
+        assert_eq!(None, scala.frequency(73));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(Scala::parse("", None).is_err());
+        assert!(Scala::parse("Empty\n0\n", None).is_err());
+        assert!(Scala::parse("Short\n3\n100.0\n", None).is_err());
+        assert!(Scala::parse("Zero\n1\n0/1\n", None).is_err());
+        assert!(Scala::parse("Text\n1\nabc\n", None).is_err());
+        assert!(Scala::parse(PENTATONIC, Some("7\n0\n127\n")).is_err());
+        assert!(Scala::parse(PENTATONIC, Some(&KBM.replace("69\n", "61\n"))).is_err());
+    }
+}